@@ -0,0 +1,85 @@
+use std::io::{self, Write};
+use std::thread;
+use std::time::Duration;
+
+const CLEAR_SCREEN: &str = "\x1b[2J";
+const CURSOR_HOME: &str = "\x1b[H";
+const CLEAR_LINE: &str = "\x1b[K";
+const CLEAR_BELOW: &str = "\x1b[J";
+const HIDE_CURSOR: &str = "\x1b[?25l";
+const SHOW_CURSOR: &str = "\x1b[?25h";
+const HIGHLIGHT: &str = "\x1b[1;33m";
+const RESET: &str = "\x1b[0m";
+
+// A single picture of a simulation. The body is usually the `Display` output
+// of the day's model, and every `highlight` character in it is drawn in color.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Frame {
+    pub(crate) caption: String,
+    pub(crate) body: String,
+    pub(crate) highlight: char,
+}
+
+pub(crate) struct Animation {
+    fps: u32,
+}
+
+impl Animation {
+    pub(crate) fn new(fps: u32) -> Self {
+        assert!(fps > 0);
+        Animation { fps }
+    }
+
+    // Redraw every frame in place, waiting between frames to match the frame
+    // rate.
+    pub(crate) fn play(&self, frames: impl IntoIterator<Item = Frame>) -> io::Result<()> {
+        let delay = Duration::from_secs_f64(1.0 / self.fps as f64);
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+
+        write!(out, "{}{}", CLEAR_SCREEN, HIDE_CURSOR)?;
+        for frame in frames {
+            write!(out, "{}{}", CURSOR_HOME, colorize(&frame))?;
+            out.flush()?;
+            thread::sleep(delay);
+        }
+        write!(out, "{}", SHOW_CURSOR)?;
+        out.flush()
+    }
+}
+
+fn colorize(frame: &Frame) -> String {
+    let mut colored = format!("{}{}\n", frame.caption, CLEAR_LINE);
+    for line in frame.body.lines() {
+        for c in line.chars() {
+            if c == frame.highlight {
+                colored.push_str(HIGHLIGHT);
+                colored.push(c);
+                colored.push_str(RESET);
+            } else {
+                colored.push(c);
+            }
+        }
+        colored.push_str(CLEAR_LINE);
+        colored.push('\n');
+    }
+    colored.push_str(CLEAR_BELOW);
+    colored
+}
+
+#[test]
+fn colorize_highlights_only_requested_char() {
+    let frame = Frame {
+        caption: "step 1".to_string(),
+        body: "#.\n".to_string(),
+        highlight: '#',
+    };
+    let expected = format!(
+        "step 1{clr}\n{hl}#{reset}.{clr}\n{below}",
+        clr = CLEAR_LINE,
+        hl = HIGHLIGHT,
+        reset = RESET,
+        below = CLEAR_BELOW
+    );
+    assert_eq!(colorize(&frame), expected);
+}
@@ -0,0 +1,151 @@
+use crate::simulation::Simulation;
+use crate::util::{self, Result};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const MAGIC: &str = "aoc-checkpoint";
+const VERSION: u32 = 1;
+
+// A simulation state that can be written to a text file and read back, to
+// stop a long run and continue it later or on another machine.
+pub(crate) trait Checkpoint: Sized {
+    // Name written in the header, so a file can't be loaded as another state
+    const KIND: &'static str;
+
+    fn to_lines(&self) -> Vec<String>;
+    fn from_lines(lines: &[&str]) -> Result<Self>;
+}
+
+pub(crate) fn to_string<T: Checkpoint>(state: &T) -> String {
+    let mut contents = format!("{} {} {}\n", MAGIC, VERSION, T::KIND);
+    for line in state.to_lines() {
+        contents.push_str(&line);
+        contents.push('\n');
+    }
+    contents
+}
+
+pub(crate) fn from_str<T: Checkpoint>(contents: &str) -> Result<T> {
+    let mut lines = contents.lines().map(str::trim).filter(|l| !l.is_empty());
+    let header = lines.next().ok_or("empty checkpoint")?;
+    match header.split_whitespace().collect::<Vec<_>>().as_slice() {
+        [MAGIC, version, kind] => {
+            if version.parse::<u32>() != Ok(VERSION) {
+                return Err(format!("unsupported checkpoint version {}", version).into());
+            }
+            if *kind != T::KIND {
+                return Err(format!("expected a {} checkpoint, found {}", T::KIND, kind).into());
+            }
+        }
+        _ => return Err("not a checkpoint file".into()),
+    }
+    T::from_lines(&lines.collect::<Vec<_>>())
+}
+
+pub(crate) fn save<T: Checkpoint>(state: &T, path: &Path) -> Result<()> {
+    // Write next to the destination first, so an interrupted save can't
+    // destroy the previous checkpoint
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, to_string(state))?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+pub(crate) fn load<T: Checkpoint>(path: &Path) -> Result<T> {
+    let contents = util::read_file(path.to_str().ok_or("invalid checkpoint path")?)
+        .map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e).into())
+}
+
+// Split a `key value...` line, checking the key
+pub(crate) fn field<'a>(line: Option<&&'a str>, key: &str) -> Result<Vec<&'a str>> {
+    let line = line.ok_or_else(|| format!("missing {}", key))?;
+    let mut words = line.split_whitespace();
+    if words.next() != Some(key) {
+        return Err(format!("expected {}, found '{}'", key, line).into());
+    }
+    Ok(words.collect())
+}
+
+// How a simulation should be run from the command line
+#[derive(Debug, Default)]
+pub(crate) struct Session {
+    pub(crate) resume: Option<PathBuf>,
+    pub(crate) save_to: Option<PathBuf>,
+    pub(crate) steps: usize,
+    // Save every N steps on top of the end of the run
+    pub(crate) every: Option<usize>,
+}
+
+impl Session {
+    // Start from the checkpoint if there is one, otherwise from `initial`, and
+    // run the steps, saving along the way. Without an explicit destination
+    // the checkpoint that was resumed is updated.
+    pub(crate) fn run<T: Checkpoint>(
+        &self,
+        initial: impl FnOnce() -> Result<T>,
+        mut step: impl FnMut(&mut T) -> Result<()>,
+    ) -> Result<T> {
+        let mut state = match &self.resume {
+            Some(path) => load(path)?,
+            None => initial()?,
+        };
+        let save_to = self.save_to.as_ref().or(self.resume.as_ref());
+        for i in 1..=self.steps {
+            step(&mut state)?;
+            if let (Some(every), Some(path)) = (self.every, save_to) {
+                if i % every == 0 && i != self.steps {
+                    save(&state, path)?;
+                }
+            }
+        }
+        if let Some(path) = save_to {
+            save(&state, path)?;
+        }
+        Ok(state)
+    }
+
+    pub(crate) fn run_simulation<T: Checkpoint + Simulation>(
+        &self,
+        initial: impl FnOnce() -> Result<T>,
+    ) -> Result<T> {
+        self.run(initial, T::step)
+    }
+}
+
+#[cfg(test)]
+struct Counter(u32);
+
+#[cfg(test)]
+impl Checkpoint for Counter {
+    const KIND: &'static str = "counter";
+
+    fn to_lines(&self) -> Vec<String> {
+        vec![format!("count {}", self.0)]
+    }
+
+    fn from_lines(lines: &[&str]) -> Result<Self> {
+        let count = field(lines.first(), "count")?;
+        Ok(Counter(count.first().ok_or("missing count")?.parse()?))
+    }
+}
+
+#[test]
+fn header_is_checked() {
+    assert_eq!(
+        to_string(&Counter(3)),
+        "aoc-checkpoint 1 counter\ncount 3\n"
+    );
+    let err = from_str::<Counter>("aoc-checkpoint 1 polymer\n")
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.to_string(),
+        "expected a counter checkpoint, found polymer"
+    );
+    let err = from_str::<Counter>("aoc-checkpoint 2 counter\n")
+        .err()
+        .unwrap();
+    assert_eq!(err.to_string(), "unsupported checkpoint version 2");
+}
@@ -0,0 +1,362 @@
+use crate::config::{OutputFormat, Overrides};
+use crate::day01::Aggregation;
+use crate::day02::UnitBounds;
+use crate::day03::Criteria;
+use crate::point::Point;
+use crate::util::Result;
+
+use std::path::PathBuf;
+
+// Day run when no argument is given, to keep `cargo run` behaving as before.
+const DEFAULT_DAY: u32 = 14;
+const DEFAULT_FPS: u32 = 10;
+const DEFAULT_PORT: u16 = 8021;
+// Slowdown from one commit to the next, in percent, reported as a regression
+const DEFAULT_THRESHOLD: f64 = 10.0;
+
+// Only one is ever built, its size doesn't matter
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, PartialEq)]
+pub(crate) enum Command {
+    Run(RunOptions),
+    Serve {
+        port: u16,
+    },
+    Config,
+    Bench {
+        day: u32,
+    },
+    PerfHistory {
+        day: u32,
+        phase: Option<String>,
+        threshold: f64,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct RunOptions {
+    pub(crate) day: u32,
+    pub(crate) animate: bool,
+    pub(crate) fps: u32,
+    pub(crate) explain: bool,
+    pub(crate) stream: bool,
+    pub(crate) input: Option<PathBuf>,
+    pub(crate) resume: Option<PathBuf>,
+    pub(crate) checkpoint: Option<PathBuf>,
+    pub(crate) checkpoint_every: Option<usize>,
+    pub(crate) steps: Option<usize>,
+    // Simulate on a world whose edges wrap around
+    pub(crate) wrap: bool,
+    // Sliding window analysis of the sonar report
+    pub(crate) window: Option<usize>,
+    pub(crate) aggregation: Option<Aggregation>,
+    // Navigation models to compare on the submarine commands
+    pub(crate) models: Option<Vec<String>>,
+    // Directory to write the submarine trajectories to
+    pub(crate) trajectory: Option<PathBuf>,
+    // Horizontal position and depth to write a submarine program for
+    pub(crate) plan: Option<Point<i64>>,
+    pub(crate) units: Option<UnitBounds>,
+    // File with the programs of several submarines to run together
+    pub(crate) fleet: Option<PathBuf>,
+    pub(crate) safety: Option<i64>,
+    // How to pick the bits of a diagnostic rating
+    pub(crate) rating: Option<Criteria>,
+}
+
+impl RunOptions {
+    pub(crate) fn new(day: u32) -> Self {
+        RunOptions {
+            day,
+            animate: false,
+            fps: DEFAULT_FPS,
+            explain: false,
+            stream: false,
+            input: None,
+            resume: None,
+            checkpoint: None,
+            checkpoint_every: None,
+            steps: None,
+            wrap: false,
+            window: None,
+            aggregation: None,
+            models: None,
+            trajectory: None,
+            plan: None,
+            units: None,
+            fleet: None,
+            safety: None,
+            rating: None,
+        }
+    }
+}
+
+// Parse the command line. Configuration flags are accepted anywhere and
+// override the values of the configuration file.
+pub(crate) fn parse(args: &[String]) -> Result<(Command, Overrides)> {
+    let (overrides, args) = parse_overrides(args)?;
+    let mut args = args.into_iter();
+    let command = match args.next() {
+        None => Command::Run(RunOptions::new(DEFAULT_DAY)),
+        Some("run") => parse_run(args)?,
+        Some("serve") => parse_serve(args)?,
+        Some("config") => Command::Config,
+        Some("bench") => {
+            let day = args.next().ok_or("missing day")?;
+            Command::Bench {
+                day: parse_value("day", day)?,
+            }
+        }
+        Some("perf") => match args.next() {
+            Some("history") => parse_perf_history(args)?,
+            _ => return Err("usage: perf history <day>".into()),
+        },
+        Some(other) => return Err(format!("unknown command: {}", other).into()),
+    };
+    Ok((command, overrides))
+}
+
+fn parse_overrides(args: &[String]) -> Result<(Overrides, Vec<&str>)> {
+    let mut overrides = Overrides::default();
+    let mut rest = Vec::new();
+    let mut args = args.iter().map(|s| s.as_str());
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value for {}", arg));
+        match arg {
+            "--config" => overrides.config = Some(PathBuf::from(value()?)),
+            "--year" => overrides.year = Some(parse_value(arg, value()?)?),
+            "--resources" => overrides.resources = Some(PathBuf::from(value()?)),
+            "--format" => overrides.format = Some(OutputFormat::parse(value()?)?),
+            "--repeat" => overrides.bench_repeat = Some(parse_value(arg, value()?)?),
+            "--session-file" => overrides.session_file = Some(PathBuf::from(value()?)),
+            _ => rest.push(arg),
+        }
+    }
+    Ok((overrides, rest))
+}
+
+fn parse_run<'a>(mut args: impl Iterator<Item = &'a str>) -> Result<Command> {
+    let day = args.next().ok_or("missing day")?;
+    let mut options = RunOptions::new(parse_value("day", day)?);
+
+    while let Some(arg) = args.next() {
+        match arg {
+            "--animate" => options.animate = true,
+            "--explain" => options.explain = true,
+            "--stream" => options.stream = true,
+            "--wrap" => options.wrap = true,
+            "--input" => {
+                let input = args.next().ok_or("missing value for --input")?;
+                options.input = Some(PathBuf::from(input));
+            }
+            "--resume" => {
+                let resume = args.next().ok_or("missing value for --resume")?;
+                options.resume = Some(PathBuf::from(resume));
+            }
+            "--checkpoint" => {
+                let checkpoint = args.next().ok_or("missing value for --checkpoint")?;
+                options.checkpoint = Some(PathBuf::from(checkpoint));
+            }
+            "--checkpoint-every" => {
+                let every = args.next().ok_or("missing value for --checkpoint-every")?;
+                let every = parse_value(arg, every)?;
+                if every == 0 {
+                    return Err("--checkpoint-every must be greater than 0".into());
+                }
+                options.checkpoint_every = Some(every);
+            }
+            "--steps" => {
+                let steps = args.next().ok_or("missing value for --steps")?;
+                options.steps = Some(parse_value(arg, steps)?);
+            }
+            "--window" => {
+                let window = args.next().ok_or("missing value for --window")?;
+                let window = parse_value(arg, window)?;
+                if window == 0 {
+                    return Err("--window must be greater than 0".into());
+                }
+                options.window = Some(window);
+            }
+            "--agg" => {
+                let aggregation = args.next().ok_or("missing value for --agg")?;
+                options.aggregation = Some(Aggregation::parse(aggregation)?);
+            }
+            "--models" => {
+                let models = args.next().ok_or("missing value for --models")?;
+                options.models = Some(models.split(',').map(str::to_string).collect());
+            }
+            "--trajectory" => {
+                let dir = args.next().ok_or("missing value for --trajectory")?;
+                options.trajectory = Some(PathBuf::from(dir));
+            }
+            "--plan" => {
+                let target = args.next().ok_or("missing value for --plan")?;
+                options.plan = Some(parse_value(arg, target)?);
+            }
+            "--units" => {
+                let units = args.next().ok_or("missing value for --units")?;
+                options.units = Some(UnitBounds::parse(units)?);
+            }
+            "--fleet" => {
+                let fleet = args.next().ok_or("missing value for --fleet")?;
+                options.fleet = Some(PathBuf::from(fleet));
+            }
+            "--safety" => {
+                let safety = args.next().ok_or("missing value for --safety")?;
+                let safety = parse_value(arg, safety)?;
+                if safety < 0 {
+                    return Err("--safety must not be negative".into());
+                }
+                options.safety = Some(safety);
+            }
+            "--rating" => {
+                let rating = args.next().ok_or("missing value for --rating")?;
+                options.rating = Some(Criteria::parse(rating)?);
+            }
+            "--fps" => {
+                let fps = args.next().ok_or("missing value for --fps")?;
+                options.fps = parse_value("--fps", fps)?;
+                if options.fps == 0 {
+                    return Err("--fps must be greater than 0".into());
+                }
+            }
+            _ => return Err(format!("unknown option: {}", arg).into()),
+        }
+    }
+    Ok(Command::Run(options))
+}
+
+fn parse_serve<'a>(mut args: impl Iterator<Item = &'a str>) -> Result<Command> {
+    let mut port = DEFAULT_PORT;
+    while let Some(arg) = args.next() {
+        match arg {
+            "--port" => {
+                let value = args.next().ok_or("missing value for --port")?;
+                port = parse_value("--port", value)?;
+            }
+            _ => return Err(format!("unknown option: {}", arg).into()),
+        }
+    }
+    Ok(Command::Serve { port })
+}
+
+fn parse_perf_history<'a>(mut args: impl Iterator<Item = &'a str>) -> Result<Command> {
+    let day = args.next().ok_or("missing day")?;
+    let day = parse_value("day", day)?;
+    let mut phase = None;
+    let mut threshold = DEFAULT_THRESHOLD;
+    while let Some(arg) = args.next() {
+        let value = args.next().ok_or(format!("missing value for {}", arg))?;
+        match arg {
+            "--phase" => phase = Some(value.to_string()),
+            "--threshold" => threshold = parse_value(arg, value)?,
+            _ => return Err(format!("unknown option: {}", arg).into()),
+        }
+    }
+    Ok(Command::PerfHistory {
+        day,
+        phase,
+        threshold,
+    })
+}
+
+fn parse_value<T: std::str::FromStr>(name: &str, value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for {}: {}", name, value).into())
+}
+
+#[cfg(test)]
+fn to_args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn parse_no_args_runs_default_day() {
+    let (command, overrides) = parse(&[]).unwrap();
+    assert_eq!(command, Command::Run(RunOptions::new(DEFAULT_DAY)));
+    assert_eq!(overrides, Overrides::default());
+}
+
+#[test]
+fn parse_config_overrides_anywhere() {
+    let args = to_args(&["--format", "csv", "run", "11", "--resources", "/tmp/inputs"]);
+    let (command, overrides) = parse(&args).unwrap();
+    assert_eq!(command, Command::Run(RunOptions::new(11)));
+    assert_eq!(overrides.format, Some(OutputFormat::Csv));
+    assert_eq!(overrides.resources, Some(PathBuf::from("/tmp/inputs")));
+}
+
+#[test]
+fn parse_run_animate() {
+    let args = to_args(&["run", "11", "--animate", "--fps", "30"]);
+    let expected = RunOptions {
+        day: 11,
+        animate: true,
+        fps: 30,
+        explain: false,
+        stream: false,
+        input: None,
+        resume: None,
+        checkpoint: None,
+        checkpoint_every: None,
+        steps: None,
+        wrap: false,
+        window: None,
+        aggregation: None,
+        models: None,
+        trajectory: None,
+        plan: None,
+        units: None,
+        fleet: None,
+        safety: None,
+        rating: None,
+    };
+    assert_eq!(parse(&args).unwrap().0, Command::Run(expected));
+}
+
+#[test]
+fn parse_run_window() {
+    let args = to_args(&["run", "1", "--window", "5", "--agg", "median"]);
+    let mut expected = RunOptions::new(1);
+    expected.window = Some(5);
+    expected.aggregation = Some(Aggregation::Median);
+    assert_eq!(parse(&args).unwrap().0, Command::Run(expected));
+    assert!(parse(&to_args(&["run", "1", "--window", "0"])).is_err());
+    assert!(parse(&to_args(&["run", "1", "--agg", "mode"])).is_err());
+}
+
+#[test]
+fn parse_run_models() {
+    let args = to_args(&["run", "2", "--models", "aim,drag"]);
+    let mut expected = RunOptions::new(2);
+    expected.models = Some(vec!["aim".to_string(), "drag".to_string()]);
+    assert_eq!(parse(&args).unwrap().0, Command::Run(expected));
+
+    let args = to_args(&["run", "2", "--plan", "15,60", "--units", "1..5"]);
+    let mut expected = RunOptions::new(2);
+    expected.plan = Some(Point::new(15, 60));
+    expected.units = Some(UnitBounds { min: 1, max: 5 });
+    assert_eq!(parse(&args).unwrap().0, Command::Run(expected));
+}
+
+#[test]
+fn parse_run_rating() {
+    let args = to_args(&["run", "3", "--rating", "most,error,rtl", "--explain"]);
+    let mut expected = RunOptions::new(3);
+    expected.rating = Some(Criteria::parse("most,error,rtl").unwrap());
+    expected.explain = true;
+    assert_eq!(parse(&args).unwrap().0, Command::Run(expected));
+    assert!(parse(&to_args(&["run", "3", "--rating", "most"])).is_err());
+}
+
+#[test]
+fn parse_perf_history_options() {
+    let args = to_args(&["perf", "history", "12", "--phase", "dfs_part2"]);
+    let expected = Command::PerfHistory {
+        day: 12,
+        phase: Some("dfs_part2".to_string()),
+        threshold: DEFAULT_THRESHOLD,
+    };
+    assert_eq!(parse(&args).unwrap().0, expected);
+}
@@ -0,0 +1,371 @@
+use crate::util::{self, Result};
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+pub(crate) const CONFIG_FILE: &str = "aoc.toml";
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum OutputFormat {
+    Text,
+    Csv,
+}
+
+impl OutputFormat {
+    pub(crate) fn parse(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "csv" => Ok(OutputFormat::Csv),
+            _ => Err(format!("unknown output format: {}", s).into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Config {
+    pub(crate) year: u32,
+    pub(crate) resources: PathBuf,
+    pub(crate) format: OutputFormat,
+    pub(crate) bench_repeat: u32,
+    pub(crate) session_file: Option<PathBuf>,
+    pub(crate) perf_history: PathBuf,
+    // File the configuration was read from, if any
+    pub(crate) source: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            year: 2021,
+            resources: PathBuf::from("./resources"),
+            format: OutputFormat::Text,
+            bench_repeat: 10,
+            session_file: None,
+            perf_history: PathBuf::from("./perf.csv"),
+            source: None,
+        }
+    }
+}
+
+// Values given on the command line, they take precedence over the file.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Overrides {
+    pub(crate) config: Option<PathBuf>,
+    pub(crate) year: Option<u32>,
+    pub(crate) resources: Option<PathBuf>,
+    pub(crate) format: Option<OutputFormat>,
+    pub(crate) bench_repeat: Option<u32>,
+    pub(crate) session_file: Option<PathBuf>,
+}
+
+impl Config {
+    // Read the file given with `--config`, or the first `aoc.toml` found in
+    // the working directory or one of its parents, then apply the overrides.
+    pub(crate) fn load(overrides: &Overrides) -> Result<Self> {
+        let path = match &overrides.config {
+            Some(path) => Some(path.clone()),
+            None => discover(&std::env::current_dir()?),
+        };
+        let mut config = match path {
+            Some(path) => Self::from_file(&path)?,
+            None => Config::default(),
+        };
+        config.apply(overrides);
+        Ok(config)
+    }
+
+    fn from_file(path: &Path) -> Result<Self> {
+        let contents = util::read_file(path.to_str().ok_or("invalid config path")?)
+            .map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        let mut config =
+            Self::parse(&contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+        // Paths in the file are relative to the file itself
+        let base = path.parent().unwrap_or_else(|| Path::new("."));
+        config.resources = base.join(&config.resources);
+        config.perf_history = base.join(&config.perf_history);
+        config.session_file = config.session_file.map(|p| base.join(expand_home(&p)));
+        config.source = Some(path.to_path_buf());
+        Ok(config)
+    }
+
+    fn parse(contents: &str) -> Result<Self> {
+        let mut config = Config::default();
+        for (key, value) in parse_toml(contents)? {
+            match key.as_str() {
+                "year" => config.year = value.as_integer(&key)?,
+                "paths.resources" => config.resources = PathBuf::from(value.as_str(&key)?),
+                "paths.perf_history" => config.perf_history = PathBuf::from(value.as_str(&key)?),
+                "output.format" => config.format = OutputFormat::parse(value.as_str(&key)?)?,
+                "bench.repeat" => config.bench_repeat = value.as_integer(&key)?,
+                "session.token_file" => {
+                    config.session_file = Some(PathBuf::from(value.as_str(&key)?))
+                }
+                _ => return Err(format!("unknown key: {}", key).into()),
+            }
+        }
+        Ok(config)
+    }
+
+    fn apply(&mut self, overrides: &Overrides) {
+        if let Some(year) = overrides.year {
+            self.year = year;
+        }
+        if let Some(resources) = &overrides.resources {
+            self.resources = resources.clone();
+        }
+        if let Some(format) = overrides.format {
+            self.format = format;
+        }
+        if let Some(repeat) = overrides.bench_repeat {
+            self.bench_repeat = repeat;
+        }
+        if let Some(session_file) = &overrides.session_file {
+            self.session_file = Some(session_file.clone());
+        }
+    }
+}
+
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let source = match &self.source {
+            Some(path) => path.display().to_string(),
+            None => "(defaults)".to_string(),
+        };
+        let session_file = match &self.session_file {
+            Some(path) => path.display().to_string(),
+            None => "(none)".to_string(),
+        };
+        let rows = [
+            ("source", source),
+            ("year", self.year.to_string()),
+            ("paths.resources", self.resources.display().to_string()),
+            (
+                "paths.perf_history",
+                self.perf_history.display().to_string(),
+            ),
+            ("output.format", format!("{:?}", self.format).to_lowercase()),
+            ("bench.repeat", self.bench_repeat.to_string()),
+            ("session.token_file", session_file),
+        ];
+        for (key, value) in rows.iter() {
+            match self.format {
+                OutputFormat::Text => writeln!(f, "{} = {}", key, value)?,
+                OutputFormat::Csv => writeln!(f, "{},{}", key, value)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+// Set the configuration used for the rest of the run. Only the first call has
+// an effect.
+pub(crate) fn init(config: Config) {
+    let _ = CONFIG.set(config);
+}
+
+pub(crate) fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+// Replace a leading `~` with the home directory
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), std::env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn discover(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|d| d.join(CONFIG_FILE))
+        .find(|p| p.is_file())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+}
+
+impl Value {
+    fn as_str(&self, key: &str) -> Result<&str> {
+        match self {
+            Value::String(s) => Ok(s),
+            _ => Err(format!("{} must be a string", key).into()),
+        }
+    }
+
+    fn as_integer<T: TryFrom<i64>>(&self, key: &str) -> Result<T> {
+        match self {
+            Value::Integer(i) => {
+                T::try_from(*i).map_err(|_| format!("{} is out of range", key).into())
+            }
+            _ => Err(format!("{} must be an integer", key).into()),
+        }
+    }
+}
+
+// The subset of TOML the configuration needs: `[section]` headers,
+// `key = value` pairs with string, integer or boolean values, and comments.
+// Keys inside a section are returned as `section.key`.
+fn parse_toml(contents: &str) -> Result<Vec<(String, Value)>> {
+    let mut section = String::new();
+    let mut entries: Vec<(String, Value)> = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        let line_number = i + 1;
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[') {
+            let name = header
+                .strip_suffix(']')
+                .ok_or_else(|| format!("line {}: unclosed section header", line_number))?
+                .trim();
+            if !is_bare_key(name) {
+                return Err(format!("line {}: invalid section name", line_number).into());
+            }
+            section = name.to_string();
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("line {}: expected key = value", line_number))?;
+        let key = key.trim();
+        if !is_bare_key(key) {
+            return Err(format!("line {}: invalid key '{}'", line_number, key).into());
+        }
+        let key = if section.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", section, key)
+        };
+        if entries.iter().any(|(k, _)| *k == key) {
+            return Err(format!("line {}: duplicate key {}", line_number, key).into());
+        }
+        let value =
+            parse_value(value.trim()).map_err(|e| format!("line {}: {}", line_number, e))?;
+        entries.push((key, value));
+    }
+    Ok(entries)
+}
+
+fn is_bare_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+// A `#` starts a comment unless it is inside a string
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => (),
+        }
+    }
+    line
+}
+
+fn parse_value(value: &str) -> Result<Value> {
+    if let Some(quoted) = value.strip_prefix('"') {
+        let quoted = quoted.strip_suffix('"').ok_or("unterminated string")?;
+        return Ok(Value::String(unescape(quoted)?));
+    }
+    match value {
+        "true" => return Ok(Value::Boolean(true)),
+        "false" => return Ok(Value::Boolean(false)),
+        _ => (),
+    }
+    value
+        .replace('_', "")
+        .parse::<i64>()
+        .map(Value::Integer)
+        .map_err(|_| format!("invalid value: {}", value).into())
+}
+
+fn unescape(s: &str) -> Result<String> {
+    let mut unescaped = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('"') => unescaped.push('"'),
+            Some('\\') => unescaped.push('\\'),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some(c) => return Err(format!("unknown escape: \\{}", c).into()),
+            None => return Err("unterminated escape".into()),
+        }
+    }
+    Ok(unescaped)
+}
+
+#[test]
+fn parse_toml_subset() {
+    let contents = r#"
+# Per-machine settings
+year = 2021
+
+[paths]
+resources = "../inputs" # kept out of the repo
+
+[bench]
+repeat = 1_000
+warmup = false
+
+[session]
+token_file = "C:\\aoc\\#session"
+"#;
+    let entries = parse_toml(contents).unwrap();
+    assert_eq!(
+        entries,
+        vec![
+            ("year".to_string(), Value::Integer(2021)),
+            (
+                "paths.resources".to_string(),
+                Value::String("../inputs".to_string())
+            ),
+            ("bench.repeat".to_string(), Value::Integer(1000)),
+            ("bench.warmup".to_string(), Value::Boolean(false)),
+            (
+                "session.token_file".to_string(),
+                Value::String("C:\\aoc\\#session".to_string())
+            ),
+        ]
+    );
+}
+
+#[test]
+fn parse_toml_reports_line() {
+    let err = parse_toml("year = 2021\n[bench]\nrepeat 3\n").unwrap_err();
+    assert_eq!(err.to_string(), "line 3: expected key = value");
+}
+
+#[test]
+fn overrides_take_precedence() {
+    let mut config = Config::parse("[output]\nformat = \"csv\"\n[bench]\nrepeat = 3\n").unwrap();
+    config.apply(&Overrides {
+        bench_repeat: Some(7),
+        ..Default::default()
+    });
+    assert_eq!(config.format, OutputFormat::Csv);
+    assert_eq!(config.bench_repeat, 7);
+    assert_eq!(config.year, 2021);
+}
@@ -1,23 +1,355 @@
-use std::fs::File;
-use std::io::prelude::*;
+use crate::util::{self, Result};
 
-pub fn day_01() -> std::io::Result<()> {
-    // let mut file = File::open("./resources/day01_small.txt")?;
-    let mut file = File::open("./resources/day01.txt")?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::io::BufRead;
+
+// Spikes are readings further than this many standard deviations from the
+// mean of the readings before them
+const SPIKE_WINDOW: usize = 10;
+const SPIKE_DEVIATIONS: f64 = 3.0;
+
+// Counts how often the sum of a sliding window grows. Two consecutive windows
+// share all but one reading, so comparing their sums is comparing the reading
+// entering the window with the one leaving it: only the last `window` readings
+// need to be kept.
+struct IncreaseCounter {
+    window: usize,
+    previous: VecDeque<i32>,
+    count: usize,
+}
+
+impl IncreaseCounter {
+    fn new(window: usize) -> Self {
+        assert!(window > 0);
+        IncreaseCounter {
+            window,
+            previous: VecDeque::with_capacity(window),
+            count: 0,
+        }
+    }
+
+    fn push(&mut self, reading: i32) {
+        if self.previous.len() == self.window {
+            let leaving = self.previous.pop_front().unwrap();
+            if leaving < reading {
+                self.count += 1;
+            }
+        }
+        self.previous.push_back(reading);
+    }
+}
+
+// How the readings of a window are reduced to a single value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Aggregation {
+    Sum,
+    Mean,
+    Min,
+    Max,
+    Median,
+}
+
+impl Aggregation {
+    pub(crate) fn parse(s: &str) -> Result<Self> {
+        match s {
+            "sum" => Ok(Aggregation::Sum),
+            "mean" => Ok(Aggregation::Mean),
+            "min" => Ok(Aggregation::Min),
+            "max" => Ok(Aggregation::Max),
+            "median" => Ok(Aggregation::Median),
+            _ => Err(format!("unknown aggregation: {}", s).into()),
+        }
+    }
+
+    fn apply(self, window: &[i32]) -> f64 {
+        let sum: f64 = window.iter().map(|&r| f64::from(r)).sum();
+        match self {
+            Aggregation::Sum => sum,
+            Aggregation::Mean => sum / window.len() as f64,
+            Aggregation::Min => f64::from(*window.iter().min().unwrap()),
+            Aggregation::Max => f64::from(*window.iter().max().unwrap()),
+            Aggregation::Median => {
+                let mut sorted = window.to_vec();
+                sorted.sort_unstable();
+                let middle = sorted.len() / 2;
+                if sorted.len().is_multiple_of(2) {
+                    (f64::from(sorted[middle - 1]) + f64::from(sorted[middle])) / 2.0
+                } else {
+                    f64::from(sorted[middle])
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Aggregation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Aggregation::Sum => "sum",
+            Aggregation::Mean => "mean",
+            Aggregation::Min => "min",
+            Aggregation::Max => "max",
+            Aggregation::Median => "median",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Compares each sliding window of a report with the one just before it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct WindowComparator {
+    pub(crate) window: usize,
+    pub(crate) aggregation: Aggregation,
+}
+
+// Windows for which the comparison held, by the index of their first reading
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Matches {
+    pub(crate) indices: Vec<usize>,
+}
+
+impl Matches {
+    pub(crate) fn count(&self) -> usize {
+        self.indices.len()
+    }
+}
+
+impl WindowComparator {
+    pub(crate) fn new(window: usize, aggregation: Aggregation) -> Self {
+        assert!(window > 0);
+        WindowComparator {
+            window,
+            aggregation,
+        }
+    }
+
+    // Windows where `holds(previous, current)` is true for the aggregated
+    // values of the previous window and this one
+    pub(crate) fn compare(&self, report: &[i32], holds: impl Fn(f64, f64) -> bool) -> Matches {
+        let values: Vec<f64> = report
+            .windows(self.window)
+            .map(|w| self.aggregation.apply(w))
+            .collect();
+        Matches {
+            indices: values
+                .windows(2)
+                .enumerate()
+                .filter(|(_, pair)| holds(pair[0], pair[1]))
+                .map(|(i, _)| i + 1)
+                .collect(),
+        }
+    }
+
+    pub(crate) fn increases(&self, report: &[i32]) -> Matches {
+        self.compare(report, |previous, current| previous < current)
+    }
+}
+
+// A line of the report without a reading from a sensor
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Gap {
+    pub(crate) line: usize,
+    pub(crate) content: String,
+}
+
+// The readings of one sensor, skipping the lines that are empty or hold
+// something else than a number
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Report {
+    pub(crate) readings: Vec<i32>,
+    // Line number of each reading, starting from 1
+    pub(crate) lines: Vec<usize>,
+    pub(crate) gaps: Vec<Gap>,
+}
+
+impl Report {
+    // One report per column of whitespace separated readings. The first line
+    // that isn't empty gives the number of sensors, lines with another
+    // number of columns are a gap for every sensor.
+    pub(crate) fn parse_sensors(contents: &str) -> Vec<Self> {
+        let lines: Vec<&str> = contents.trim_end().lines().collect();
+        let sensors = lines
+            .iter()
+            .map(|line| line.split_whitespace().count())
+            .find(|&columns| columns > 0)
+            .unwrap_or(1);
+        let mut reports = vec![Report::default(); sensors];
+        for (i, line) in lines.iter().enumerate() {
+            let columns: Vec<&str> = line.split_whitespace().collect();
+            if columns.len() != sensors {
+                for report in &mut reports {
+                    report.push_gap(i + 1, line);
+                }
+                continue;
+            }
+            for (report, column) in reports.iter_mut().zip(columns) {
+                match column.parse::<i32>() {
+                    Ok(reading) => {
+                        report.readings.push(reading);
+                        report.lines.push(i + 1);
+                    }
+                    Err(_) => report.push_gap(i + 1, column),
+                }
+            }
+        }
+        reports
+    }
+
+    fn push_gap(&mut self, line: usize, content: &str) {
+        self.gaps.push(Gap {
+            line,
+            content: content.to_string(),
+        });
+    }
+
+    fn print_gaps(&self) {
+        for gap in &self.gaps {
+            println!("line {}: no reading in {:?}", gap.line, gap.content);
+        }
+    }
+
+    // Longest strictly increasing, strictly decreasing and constant runs of
+    // readings, and readings far from the mean of the `window` before them
+    pub(crate) fn anomalies(&self, window: usize, deviations: f64) -> Anomalies {
+        let runs = |holds: fn(i32, i32) -> bool| -> Vec<Run> {
+            let mut runs = Vec::new();
+            let mut start = 0;
+            for i in 1..=self.readings.len() {
+                if i == self.readings.len() || !holds(self.readings[i - 1], self.readings[i]) {
+                    if i - start > 1 {
+                        runs.push(Run {
+                            start,
+                            len: i - start,
+                        });
+                    }
+                    start = i;
+                }
+            }
+            runs
+        };
+        // The first of the longest runs
+        let longest = |runs: Vec<Run>| runs.into_iter().rev().max_by_key(|r| r.len);
+        Anomalies {
+            increasing: longest(runs(|a, b| a < b)),
+            decreasing: longest(runs(|a, b| a > b)),
+            plateaus: runs(|a, b| a == b),
+            spikes: self.spikes(window, deviations),
+        }
+    }
+
+    fn spikes(&self, window: usize, deviations: f64) -> Vec<Spike> {
+        assert!(window > 0);
+        self.readings
+            .windows(window + 1)
+            .enumerate()
+            .filter_map(|(start, w)| {
+                let (before, reading) = (&w[..window], w[window]);
+                let mean = Aggregation::Mean.apply(before);
+                let variance = before
+                    .iter()
+                    .map(|&r| (f64::from(r) - mean).powi(2))
+                    .sum::<f64>()
+                    / window as f64;
+                let distance = (f64::from(reading) - mean).abs();
+                (distance > deviations * variance.sqrt()).then_some(Spike {
+                    index: start + window,
+                    mean,
+                    deviations: distance / variance.sqrt(),
+                })
+            })
+            .collect()
+    }
+}
+
+// Consecutive readings, by index in the report
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Run {
+    pub(crate) start: usize,
+    pub(crate) len: usize,
+}
+
+// A reading far from the rolling mean. `deviations` is infinite when the
+// readings before it were all the same.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Spike {
+    pub(crate) index: usize,
+    pub(crate) mean: f64,
+    pub(crate) deviations: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Anomalies {
+    pub(crate) increasing: Option<Run>,
+    pub(crate) decreasing: Option<Run>,
+    pub(crate) plateaus: Vec<Run>,
+    pub(crate) spikes: Vec<Spike>,
+}
+
+impl Anomalies {
+    fn print(&self, report: &Report) {
+        let lines = |run: &Run| {
+            format!(
+                "lines {}-{}, {} readings",
+                report.lines[run.start],
+                report.lines[run.start + run.len - 1],
+                run.len
+            )
+        };
+        if let Some(run) = &self.increasing {
+            println!("longest increase: {}", lines(run));
+        }
+        if let Some(run) = &self.decreasing {
+            println!("longest decrease: {}", lines(run));
+        }
+        for run in &self.plateaus {
+            println!("plateau at {}: {}", report.readings[run.start], lines(run));
+        }
+        for spike in &self.spikes {
+            println!(
+                "spike on line {}: {} is {:.1} deviations from {:.1}",
+                report.lines[spike.index],
+                report.readings[spike.index],
+                spike.deviations,
+                spike.mean
+            );
+        }
+    }
+}
+
+pub fn day_01() -> Result<()> {
+    // let contents = util::read_input("day01_small.txt")?;
+    let contents = util::read_input("day01.txt")?;
 
     println!("contents:\n{}", contents);
 
-    let report: Vec<i32> = contents
-        .trim()
-        .split('\n')
-        .map(|line| line.trim().parse::<i32>().unwrap())
-        .collect();
+    let sensors = Report::parse_sensors(&contents);
+    for (i, a) in sensors.iter().enumerate() {
+        for (j, b) in sensors.iter().enumerate().skip(i + 1) {
+            if let Some(r) = correlation(a, b) {
+                println!("sensors {} and {}: correlation {:.3}", i + 1, j + 1, r);
+            }
+        }
+    }
+    for (i, report) in sensors.iter().enumerate() {
+        if sensors.len() > 1 {
+            println!("sensor {}:", i + 1);
+        }
+        summarize(report);
+    }
+
+    Ok(())
+}
 
-    let increase_count = report
-        .windows(2)
-        .filter(|entries| entries[0] < entries[1])
+// Print the anomalies and both increase counts of one sensor
+fn summarize(report: &Report) {
+    report.print_gaps();
+    report
+        .anomalies(SPIKE_WINDOW, SPIKE_DEVIATIONS)
+        .print(report);
+
+    let increase_count = WindowComparator::new(1, Aggregation::Sum)
+        .increases(&report.readings)
         .count();
 
     println!(
@@ -25,18 +357,161 @@ pub fn day_01() -> std::io::Result<()> {
         increase_count,
     );
 
-    let increase3_count = report
-        .windows(3)
-        .map(|entries| entries.iter().sum())
-        .collect::<Vec<i32>>()
-        .windows(2)
-        .filter(|entries| entries[0] < entries[1])
+    let increase3_count = WindowComparator::new(3, Aggregation::Sum)
+        .increases(&report.readings)
         .count();
 
     println!(
         "There are {} sums that are larger than the previous sum",
         increase3_count,
     );
+}
+
+// Pearson correlation of the readings of two sensors, on the lines where both
+// have one. `None` when there are too few of them or one sensor is constant.
+pub(crate) fn correlation(a: &Report, b: &Report) -> Option<f64> {
+    let b_readings: HashMap<usize, i32> = b
+        .lines
+        .iter()
+        .copied()
+        .zip(b.readings.iter().copied())
+        .collect();
+    let pairs: Vec<(f64, f64)> = a
+        .lines
+        .iter()
+        .zip(&a.readings)
+        .filter_map(|(line, &x)| Some((f64::from(x), f64::from(*b_readings.get(line)?))))
+        .collect();
+    if pairs.len() < 2 {
+        return None;
+    }
+    let n = pairs.len() as f64;
+    let mean_x = pairs.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = pairs.iter().map(|p| p.1).sum::<f64>() / n;
+    let (mut cov, mut var_x, mut var_y) = (0.0, 0.0, 0.0);
+    for (x, y) in pairs {
+        cov += (x - mean_x) * (y - mean_y);
+        var_x += (x - mean_x).powi(2);
+        var_y += (y - mean_y).powi(2);
+    }
+    (var_x > 0.0 && var_y > 0.0).then(|| cov / (var_x * var_y).sqrt())
+}
+
+// Count the windows whose aggregated value is larger than the previous one's,
+// for each sensor
+pub fn analyze(comparator: WindowComparator) -> Result<()> {
+    let sensors = Report::parse_sensors(&util::read_input("day01.txt")?);
+    for (i, report) in sensors.iter().enumerate() {
+        if sensors.len() > 1 {
+            println!("sensor {}:", i + 1);
+        }
+        report.print_gaps();
+        let matches = comparator.increases(&report.readings);
+        println!(
+            "There are {} windows of {} with a {} larger than the previous one",
+            matches.count(),
+            comparator.window,
+            comparator.aggregation,
+        );
+        println!("at readings: {:?}", matches.indices);
+    }
+    Ok(())
+}
+
+// Same answers as `day_01`, reading the report one line at a time.
+pub fn day_01_streaming(reader: impl BufRead) -> Result<()> {
+    let mut increases = IncreaseCounter::new(1);
+    let mut increases3 = IncreaseCounter::new(3);
+    util::for_each_line(reader, |_, line| {
+        let reading = line.parse::<i32>()?;
+        increases.push(reading);
+        increases3.push(reading);
+        Ok(())
+    })?;
+
+    println!(
+        "There are {} measurements that are larger than the previous one",
+        increases.count,
+    );
+    println!(
+        "There are {} sums that are larger than the previous sum",
+        increases3.count,
+    );
+    Ok(())
+}
+
+#[test]
+fn increase_counter_matches_windows() {
+    let report = [199, 200, 208, 210, 200, 207, 240, 269, 260, 263];
+    let mut increases = IncreaseCounter::new(1);
+    let mut increases3 = IncreaseCounter::new(3);
+    for reading in report {
+        increases.push(reading);
+        increases3.push(reading);
+    }
+    assert_eq!(increases.count, 7);
+    assert_eq!(increases3.count, 5);
+}
+
+#[test]
+fn window_comparator_aggregations() {
+    let report = [199, 200, 208, 210, 200, 207, 240, 269, 260, 263];
+    let sums = WindowComparator::new(3, Aggregation::Sum);
+    assert_eq!(sums.increases(&report).count(), 5);
+    assert_eq!(
+        WindowComparator::new(1, Aggregation::Max)
+            .increases(&report)
+            .indices,
+        vec![1, 2, 3, 5, 6, 7, 9]
+    );
+    let medians = WindowComparator::new(4, Aggregation::Median);
+    assert_eq!(medians.increases(&report).indices, vec![2, 3, 4, 5, 6]);
+    let decreases = WindowComparator::new(2, Aggregation::Mean).compare(&report, |a, b| a > b);
+    assert_eq!(decreases.indices, vec![3, 4, 8]);
+    assert!(Aggregation::parse("mode").is_err());
+}
+
+#[test]
+fn anomalies_and_gaps() {
+    let report =
+        Report::parse_sensors("199\n200\n208\n\n210\n200\nabc\n200\n207\n240\n269\n260\n263\n")
+            .remove(0);
+    assert_eq!(
+        report.gaps,
+        vec![
+            Gap {
+                line: 4,
+                content: String::new()
+            },
+            Gap {
+                line: 7,
+                content: "abc".to_string()
+            },
+        ]
+    );
+    assert_eq!(report.lines[3..5], [5, 6]);
+    let anomalies = report.anomalies(3, 3.0);
+    assert_eq!(anomalies.increasing, Some(Run { start: 0, len: 4 }));
+    assert_eq!(anomalies.decreasing, Some(Run { start: 3, len: 2 }));
+    assert_eq!(anomalies.plateaus, vec![Run { start: 4, len: 2 }]);
+    // 240 jumps away from 200, 200 and 207, and 269 away from 200, 207 and 240
+    let spikes: Vec<usize> = anomalies.spikes.iter().map(|s| s.index).collect();
+    assert_eq!(spikes, vec![7, 8]);
+}
+
+#[test]
+fn multiple_sensors() {
+    let sensors = Report::parse_sensors("1 10 5\n2 20 5\n3 x 4\n4 40\n5 50 2\n");
+    assert_eq!(sensors.len(), 3);
+    assert_eq!(sensors[0].readings, vec![1, 2, 3, 5]);
+    assert_eq!(sensors[1].lines, vec![1, 2, 5]);
+    let gap_lines: Vec<usize> = sensors[1].gaps.iter().map(|g| g.line).collect();
+    assert_eq!(gap_lines, vec![3, 4]);
+    assert_eq!(sensors[2].gaps[0].content, "4 40");
 
-    return Ok(());
+    let r = correlation(&sensors[0], &sensors[1]).unwrap();
+    assert!((r - 1.0).abs() < 1e-9);
+    assert!(correlation(&sensors[0], &sensors[2]).unwrap() < -0.9);
+    let constant = Report::parse_sensors("7\n7\n7\n").remove(0);
+    assert_eq!(correlation(&sensors[0], &constant), None);
 }
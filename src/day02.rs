@@ -1,93 +1,1237 @@
-use std::fs::File;
-use std::io::prelude::*;
+use crate::point::Point;
+use crate::util;
 
-#[derive(Debug)]
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::BufRead;
+use std::path::Path;
+
+// Plan states to visit before giving up on reaching a target
+const PLAN_LIMIT: usize = 1_000_000;
+// Steps a fleet may run before its programs are considered endless
+const FLEET_STEP_LIMIT: usize = 10_000_000;
+const DRAG_PERCENT: i64 = 10;
+const MAX_DESCENT_RATE: i64 = 3;
+// Size of the exported trajectory pictures
+const SVG_WIDTH: u32 = 800;
+const SVG_HEIGHT: u32 = 400;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 enum CommandType {
     Forward,
     Down,
     Up,
+    Back,
+    // Stay in place
+    Hold,
+    // Go straight to the depth given as unit
+    DiveTo,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 struct Command {
     command_type: CommandType,
     unit: i32,
 }
 
+// Written the way `parse_program` reads it
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self.command_type {
+            CommandType::Forward => "forward",
+            CommandType::Down => "down",
+            CommandType::Up => "up",
+            CommandType::Back => "back",
+            CommandType::Hold => return write!(f, "hold"),
+            CommandType::DiveTo => "dive-to",
+        };
+        write!(f, "{} {}", name, self.unit)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Statement {
+    Command(Command),
+    Repeat { count: u32, body: Vec<Statement> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Number(i64),
+    OpenBrace,
+    CloseBrace,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "{:?}", word),
+            Token::Number(n) => write!(f, "{}", n),
+            Token::OpenBrace => write!(f, "{{"),
+            Token::CloseBrace => write!(f, "}}"),
+        }
+    }
+}
+
+// Where a token or an error starts, counting from 1
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Location {
+    line: usize,
+    column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ParseError {
+    location: Location,
+    message: String,
+    // The program stopped in the middle of a statement, more lines could
+    // still make it valid
+    incomplete: bool,
+}
+
+impl ParseError {
+    // The same error in a program starting `lines` lines further down
+    fn shifted(mut self, lines: usize) -> Self {
+        self.location.line += lines;
+        self
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.location.line, self.location.column, self.message
+        )
+    }
+}
+
+impl Error for ParseError {}
+
+fn error<T>(location: Location, message: impl Into<String>) -> Result<T, ParseError> {
+    Err(ParseError {
+        location,
+        message: message.into(),
+        incomplete: false,
+    })
+}
+
+// Error for a program that ends too early
+fn unfinished<T>(location: Location, message: impl Into<String>) -> Result<T, ParseError> {
+    Err(ParseError {
+        location,
+        message: message.into(),
+        incomplete: true,
+    })
+}
+
+// Split a program into words, numbers and braces. `#` starts a comment that
+// runs to the end of the line.
+fn tokenize(source: &str) -> Result<Vec<(Token, Location)>, ParseError> {
+    let mut tokens = Vec::new();
+    for (i, line) in source.lines().enumerate() {
+        let line = line.split('#').next().unwrap();
+        let mut chars = line.char_indices().peekable();
+        while let Some((start, c)) = chars.next() {
+            let location = Location {
+                line: i + 1,
+                column: line[..start].chars().count() + 1,
+            };
+            let mut end = start + c.len_utf8();
+            let mut take_while = |accept: fn(char) -> bool| {
+                while let Some(&(i, c)) = chars.peek() {
+                    if !accept(c) {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                end
+            };
+            let token = match c {
+                _ if c.is_whitespace() => continue,
+                '{' => Token::OpenBrace,
+                '}' => Token::CloseBrace,
+                _ if c.is_ascii_digit() => {
+                    let end = take_while(|c| c.is_ascii_digit());
+                    match line[start..end].parse() {
+                        Ok(n) => Token::Number(n),
+                        Err(_) => return error(location, "number is too large"),
+                    }
+                }
+                _ if c.is_alphabetic() => {
+                    let end = take_while(|c| c.is_alphanumeric() || c == '-');
+                    Token::Word(line[start..end].to_string())
+                }
+                _ => return error(location, format!("unexpected character {:?}", c)),
+            };
+            tokens.push((token, location));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: std::vec::IntoIter<(Token, Location)>,
+    // Where the last token started, for errors at the end of the program
+    end: Location,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<(Token, Location)> {
+        let next = self.tokens.next();
+        if let Some((_, location)) = &next {
+            self.end = *location;
+        }
+        next
+    }
+
+    fn number(&mut self, what: &str) -> Result<i64, ParseError> {
+        match self.next() {
+            Some((Token::Number(n), _)) => Ok(n),
+            Some((token, location)) => {
+                error(location, format!("expected {}, found {}", what, token))
+            }
+            None => unfinished(self.end, format!("expected {}", what)),
+        }
+    }
+
+    // Statements up to the end of the program, or of the block when `block`
+    // is where it was opened
+    fn statements(&mut self, block: Option<Location>) -> Result<Vec<Statement>, ParseError> {
+        let mut statements = Vec::new();
+        loop {
+            let (token, location) = match self.next() {
+                Some(next) => next,
+                None => match block {
+                    Some(open) => return unfinished(open, "unclosed {"),
+                    None => return Ok(statements),
+                },
+            };
+            let word = match token {
+                Token::CloseBrace if block.is_some() => return Ok(statements),
+                Token::Word(word) => word,
+                token => return error(location, format!("expected a command, found {}", token)),
+            };
+            let command_type = match word.as_str() {
+                "forward" => CommandType::Forward,
+                "up" => CommandType::Up,
+                "down" => CommandType::Down,
+                "back" => CommandType::Back,
+                "dive-to" => CommandType::DiveTo,
+                "hold" => {
+                    statements.push(Statement::Command(Command {
+                        command_type: CommandType::Hold,
+                        unit: 0,
+                    }));
+                    continue;
+                }
+                "repeat" => {
+                    let count = self.number("a repeat count")?;
+                    let count = u32::try_from(count)
+                        .or_else(|_| error(location, "repeat count is too large"))?;
+                    match self.next() {
+                        Some((Token::OpenBrace, open)) => {
+                            let body = self.statements(Some(open))?;
+                            statements.push(Statement::Repeat { count, body });
+                        }
+                        Some((_, location)) => return error(location, "expected {"),
+                        None => return unfinished(self.end, "expected {"),
+                    }
+                    continue;
+                }
+                _ => return error(location, format!("unknown command {:?}", word)),
+            };
+            let unit = self.number("a number")?;
+            let unit = i32::try_from(unit)
+                .or_else(|_| error(location, format!("{} is too large", unit)))?;
+            statements.push(Statement::Command(Command { command_type, unit }));
+        }
+    }
+}
+
+fn parse_program(source: &str) -> Result<Vec<Statement>, ParseError> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?.into_iter(),
+        end: Location { line: 1, column: 1 },
+    };
+    parser.statements(None)
+}
+
+// The commands a program runs, one at a time and in order, without
+// expanding the repeat blocks first
+struct Cursor<'a> {
+    // Blocks being run, innermost last, with the index of their next
+    // statement and how many more times they run after this one
+    blocks: Vec<(&'a [Statement], usize, u32)>,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(program: &'a [Statement]) -> Self {
+        Cursor {
+            blocks: vec![(program, 0, 0)],
+        }
+    }
+}
+
+impl<'a> Iterator for Cursor<'a> {
+    type Item = &'a Command;
+
+    fn next(&mut self) -> Option<&'a Command> {
+        loop {
+            let (block, index, runs_left) = self.blocks.last_mut()?;
+            let block: &'a [Statement] = block;
+            match block.get(*index) {
+                Some(statement) => {
+                    *index += 1;
+                    match statement {
+                        Statement::Command(command) => return Some(command),
+                        Statement::Repeat { count, body } if *count > 0 => {
+                            self.blocks.push((body, 0, count - 1))
+                        }
+                        Statement::Repeat { .. } => (),
+                    }
+                }
+                None if *runs_left > 0 => {
+                    *runs_left -= 1;
+                    *index = 0;
+                }
+                None => {
+                    self.blocks.pop();
+                }
+            }
+        }
+    }
+}
+
+// Call `f` with every command the program runs, in order
+fn interpret(program: &[Statement], f: &mut impl FnMut(&Command)) {
+    for statement in program {
+        match statement {
+            Statement::Command(command) => f(command),
+            Statement::Repeat { count, body } => {
+                for _ in 0..*count {
+                    interpret(body, f);
+                }
+            }
+        }
+    }
+}
+
+// How commands move the submarine
+trait NavigationModel {
+    fn apply(&mut self, command: &Command);
+
+    // Horizontal position and depth
+    fn position(&self) -> (i64, i64);
+
+    fn aim(&self) -> Option<i64> {
+        None
+    }
+}
+
+#[derive(Default)]
 struct Position {
-    x: i32,
-    y: i32,
+    x: i64,
+    y: i64,
 }
 
+#[derive(Default)]
 struct NewPosition {
-    depth: i32,
-    hori_pos: i32,
-    aim: i32,
+    depth: i64,
+    hori_pos: i64,
+    aim: i64,
 }
 
-pub fn day_02() -> std::io::Result<()> {
-    //let mut file = File::open("./resources/day02_small.txt")?;
-    let mut file = File::open("./resources/day02.txt")?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
+// Aim that fades by `percent` after every move forward, as if the water
+// pushed the nose of the submarine back to level
+struct AimWithDrag {
+    aim: NewPosition,
+    percent: i64,
+}
 
-    //println!("contents:\n{}", contents);
+// Aim where the depth changes by at most `max_rate` per unit moved
+struct CappedDescent {
+    aim: NewPosition,
+    max_rate: i64,
+}
+
+impl NavigationModel for Position {
+    fn apply(&mut self, command: &Command) {
+        let unit = command.unit as i64;
+        match command.command_type {
+            CommandType::Forward => self.x += unit,
+            CommandType::Back => self.x -= unit,
+            CommandType::Up => self.y -= unit,
+            CommandType::Down => self.y += unit,
+            CommandType::DiveTo => self.y = unit,
+            CommandType::Hold => (),
+        }
+    }
+
+    fn position(&self) -> (i64, i64) {
+        (self.x, self.y)
+    }
+}
+
+impl NavigationModel for NewPosition {
+    fn apply(&mut self, command: &Command) {
+        let unit = command.unit as i64;
+        match command.command_type {
+            CommandType::Down => self.aim += unit,
+            CommandType::Up => self.aim -= unit,
+            CommandType::Forward => {
+                self.hori_pos += unit;
+                self.depth += self.aim * unit;
+            }
+            // Backing up retraces the way forward
+            CommandType::Back => {
+                self.hori_pos -= unit;
+                self.depth -= self.aim * unit;
+            }
+            CommandType::DiveTo => self.depth = unit,
+            CommandType::Hold => (),
+        }
+    }
+
+    fn position(&self) -> (i64, i64) {
+        (self.hori_pos, self.depth)
+    }
+
+    fn aim(&self) -> Option<i64> {
+        Some(self.aim)
+    }
+}
+
+impl NavigationModel for AimWithDrag {
+    fn apply(&mut self, command: &Command) {
+        self.aim.apply(command);
+        if command.command_type == CommandType::Forward {
+            self.aim.aim -= self.aim.aim * self.percent / 100;
+        }
+    }
+
+    fn position(&self) -> (i64, i64) {
+        self.aim.position()
+    }
+
+    fn aim(&self) -> Option<i64> {
+        self.aim.aim()
+    }
+}
+
+impl NavigationModel for CappedDescent {
+    fn apply(&mut self, command: &Command) {
+        // Only moving is capped, turning keeps the whole aim
+        let unit = command.unit as i64;
+        let rate = self.aim.aim.clamp(-self.max_rate, self.max_rate);
+        match command.command_type {
+            CommandType::Forward => {
+                self.aim.hori_pos += unit;
+                self.aim.depth += rate * unit;
+            }
+            CommandType::Back => {
+                self.aim.hori_pos -= unit;
+                self.aim.depth -= rate * unit;
+            }
+            _ => self.aim.apply(command),
+        }
+    }
+
+    fn position(&self) -> (i64, i64) {
+        self.aim.position()
+    }
+
+    fn aim(&self) -> Option<i64> {
+        self.aim.aim()
+    }
+}
+
+type NewModel = fn() -> Box<dyn NavigationModel>;
+
+// Models that can be selected by name. A new model is registered by adding
+// it to this table.
+const MODELS: [(&str, NewModel); 4] = [
+    ("simple", || Box::new(Position::default())),
+    ("aim", || Box::new(NewPosition::default())),
+    ("drag", || {
+        Box::new(AimWithDrag {
+            aim: NewPosition::default(),
+            percent: DRAG_PERCENT,
+        })
+    }),
+    ("capped", || {
+        Box::new(CappedDescent {
+            aim: NewPosition::default(),
+            max_rate: MAX_DESCENT_RATE,
+        })
+    }),
+];
 
-    let commands: Vec<&str> = contents.trim().split('\n').collect();
+// The models the puzzle asks for
+pub(crate) const PUZZLE_MODELS: [&str; 2] = ["simple", "aim"];
 
-    let commands = commands
+fn select_models(
+    names: &[impl AsRef<str>],
+) -> util::Result<Vec<(&'static str, Box<dyn NavigationModel>)>> {
+    names
         .iter()
-        .map(|&command| parse_command(command).unwrap())
-        .collect::<Vec<Command>>();
+        .map(|name| {
+            let name = name.as_ref();
+            match MODELS.iter().find(|(n, _)| *n == name) {
+                Some((name, new)) => Ok((*name, new())),
+                None => {
+                    let known: Vec<&str> = MODELS.iter().map(|(n, _)| *n).collect();
+                    Err(format!(
+                        "unknown navigation model {} (expected one of {})",
+                        name,
+                        known.join(", ")
+                    )
+                    .into())
+                }
+            }
+        })
+        .collect()
+}
+
+// Run the program once, applying every command to all the models. Returns
+// the deepest point of each model and the first step reaching it, without
+// keeping the steps around.
+fn evaluate(
+    program: &[Statement],
+    models: &mut [(&str, Box<dyn NavigationModel>)],
+) -> Vec<(usize, i64)> {
+    let mut deepest: Vec<(usize, i64)> = models.iter().map(|(_, m)| (0, m.position().1)).collect();
+    let mut step = 0;
+    interpret(program, &mut |command| {
+        step += 1;
+        for ((_, model), max) in models.iter_mut().zip(&mut deepest) {
+            model.apply(command);
+            let depth = model.position().1;
+            if depth > max.1 {
+                *max = (step, depth);
+            }
+        }
+    });
+    deepest
+}
+
+// State of a model after a command
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Sample {
+    position: i64,
+    depth: i64,
+    aim: Option<i64>,
+}
 
-    //println!("commands:\n{:?}", commands);
+impl Sample {
+    fn of(model: &dyn NavigationModel) -> Self {
+        let (position, depth) = model.position();
+        Sample {
+            position,
+            depth,
+            aim: model.aim(),
+        }
+    }
+}
 
+// Where a model went: the first sample is before any command, then one
+// sample per command run
+#[derive(Debug, Clone, PartialEq)]
+struct Trajectory {
+    samples: Vec<Sample>,
+}
+
+impl Trajectory {
+    // Deepest point and the first step reaching it
+    fn max_depth(&self) -> (usize, i64) {
+        let (step, sample) = self
+            .samples
+            .iter()
+            .enumerate()
+            .rev()
+            .max_by_key(|(_, s)| s.depth)
+            .unwrap();
+        (step, sample.depth)
+    }
+
+    // One line per sample, the aim is left empty for models without one
+    fn to_csv(&self) -> String {
+        let mut csv = String::from("step,position,depth,aim\n");
+        for (step, s) in self.samples.iter().enumerate() {
+            let aim = s.aim.map(|a| a.to_string()).unwrap_or_default();
+            csv += &format!("{},{},{},{}\n", step, s.position, s.depth, aim);
+        }
+        csv
+    }
+
+    // Depth against horizontal position, deeper is lower. Both axes are
+    // stretched to fill the picture, depths are much larger than positions
+    // with aim.
+    fn to_svg(&self) -> String {
+        let min_x = self.samples.iter().map(|s| s.position).min().unwrap();
+        let max_x = self.samples.iter().map(|s| s.position).max().unwrap();
+        let min_y = self.samples.iter().map(|s| s.depth).min().unwrap();
+        let max_y = self.samples.iter().map(|s| s.depth).max().unwrap();
+        let points: Vec<String> = self
+            .samples
+            .iter()
+            .map(|s| format!("{},{}", s.position, s.depth))
+            .collect();
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+             viewBox=\"{} {} {} {}\" preserveAspectRatio=\"none\">\n\
+             <polyline fill=\"none\" stroke=\"black\" vector-effect=\"non-scaling-stroke\" \
+             points=\"{}\"/>\n</svg>\n",
+            SVG_WIDTH,
+            SVG_HEIGHT,
+            min_x,
+            min_y,
+            (max_x - min_x).max(1),
+            (max_y - min_y).max(1),
+            points.join(" ")
+        )
+    }
+}
+
+// Run the program once like `evaluate`, recording every model after each
+// command
+fn record(
+    program: &[Statement],
+    models: &mut [(&str, Box<dyn NavigationModel>)],
+) -> Vec<Trajectory> {
+    let mut trajectories: Vec<Trajectory> = models
+        .iter()
+        .map(|(_, model)| Trajectory {
+            samples: vec![Sample::of(model.as_ref())],
+        })
+        .collect();
+    interpret(program, &mut |command| {
+        for ((_, model), trajectory) in models.iter_mut().zip(&mut trajectories) {
+            model.apply(command);
+            trajectory.samples.push(Sample::of(model.as_ref()));
+        }
+    });
+    trajectories
+}
+
+fn print_positions(models: &[(&str, Box<dyn NavigationModel>)]) {
+    for (name, model) in models {
+        let (x, depth) = model.position();
+        println!(
+            "Final depth calculated with {} model: position {}, depth {}, product {}",
+            name,
+            x,
+            depth,
+            x * depth
+        );
+    }
+}
+
+pub fn day_02() -> util::Result<()> {
+    compare_models(&PUZZLE_MODELS, None)
+}
+
+// Run the selected models, writing the trajectory of each to
+// `<model>.csv` and `<model>.svg` in `export` if given
+pub fn compare_models(names: &[impl AsRef<str>], export: Option<&Path>) -> util::Result<()> {
+    //let contents = util::read_input("day02_small.txt")?;
+    let contents = util::read_input("day02.txt")?;
+
+    //println!("contents:\n{}", contents);
+
+    let program = parse_program(&contents)?;
+
+    //println!("program:\n{:?}", program);
+
+    let mut models = select_models(names)?;
+    // Trajectories hold a sample per command, only build them to export them
+    let deepest = match export {
+        Some(dir) => {
+            let trajectories = record(&program, &mut models);
+            fs::create_dir_all(dir)?;
+            for ((name, _), trajectory) in models.iter().zip(&trajectories) {
+                fs::write(dir.join(format!("{}.csv", name)), trajectory.to_csv())?;
+                fs::write(dir.join(format!("{}.svg", name)), trajectory.to_svg())?;
+            }
+            trajectories.iter().map(Trajectory::max_depth).collect()
+        }
+        None => evaluate(&program, &mut models),
+    };
+    for ((name, _), (step, depth)) in models.iter().zip(deepest) {
+        println!(
+            "{} model reached its max depth {} at step {}",
+            name, depth, step
+        );
+    }
+    print_positions(&models);
+    Ok(())
+}
+
+// Units of the puzzle inputs
+pub(crate) const PUZZLE_UNITS: UnitBounds = UnitBounds { min: 1, max: 9 };
+
+// Smallest and largest unit a planned command may have
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct UnitBounds {
+    pub(crate) min: i32,
+    pub(crate) max: i32,
+}
+
+impl UnitBounds {
+    // Parse `min..max`, both included
+    pub(crate) fn parse(s: &str) -> util::Result<Self> {
+        let (min, max) = s
+            .split_once("..")
+            .ok_or_else(|| format!("expected min..max: {}", s))?;
+        let bounds = UnitBounds {
+            min: min.parse()?,
+            max: max.parse()?,
+        };
+        if bounds.min < 1 || bounds.min > bounds.max {
+            return Err(format!("invalid unit bounds: {}", s).into());
+        }
+        Ok(bounds)
+    }
+}
+
+// What is left to plan: the horizontal distance to go, and the depth still
+// missing if the aim stayed as it is. Changing the aim by `d` with `h` to go
+// adds `d * h` to the final depth, so the aim itself doesn't matter.
+type PlanState = (i64, i64);
+
+// Fewest commands left from `state`, never more than needed: each forward
+// goes at most `max`, and each aim change adds at most `max * h`. `None` when
+// `h` isn't a sum of units, `k` forwards cover exactly `k * min..=k * max`.
+fn commands_left((h, need): PlanState, units: UnitBounds) -> Option<i64> {
+    let (min, max) = (i64::from(units.min), i64::from(units.max));
+    if h == 0 {
+        return (need == 0).then_some(0);
+    }
+    let forwards = (h + max - 1) / max;
+    if forwards > h / min {
+        return None;
+    }
+    let aim_changes = (need.abs() + h - 1) / h;
+    Some(forwards + (aim_changes + max - 1) / max)
+}
+
+// Shortest list of `forward`, `up` and `down` commands taking the aim model
+// from the surface to `target`, with A* on the plan states. Gives up after
+// visiting `PLAN_LIMIT` states, as some targets can't be reached.
+fn plan(target: Point<i64>, units: UnitBounds) -> Option<Vec<Command>> {
+    let start = (target.x, target.y);
+    if target.x < 0 {
+        return None;
+    }
+    let mut best: HashMap<PlanState, i64> = HashMap::from([(start, 0)]);
+    let mut previous: HashMap<PlanState, (PlanState, Command)> = HashMap::new();
+    // Among equal estimates, go on with the plan that got furthest
+    let mut queue = BinaryHeap::from([Reverse((commands_left(start, units)?, Reverse(0), start))]);
+    let mut visited = 0;
+    while let Some(Reverse((_, Reverse(cost), state))) = queue.pop() {
+        if state == (0, 0) {
+            let mut commands = Vec::new();
+            let mut state = state;
+            while let Some(&(from, command)) = previous.get(&state) {
+                commands.push(command);
+                state = from;
+            }
+            commands.reverse();
+            return Some(commands);
+        }
+        if cost > best[&state] {
+            continue;
+        }
+        visited += 1;
+        if visited > PLAN_LIMIT {
+            return None;
+        }
+        let (h, need) = state;
+        for unit in units.min..=units.max {
+            let u = i64::from(unit);
+            let mut moves = vec![
+                (CommandType::Down, (h, need - u * h)),
+                (CommandType::Up, (h, need + u * h)),
+            ];
+            if u <= h {
+                moves.push((CommandType::Forward, (h - u, need)));
+            }
+            for (command_type, next) in moves {
+                let Some(left) = commands_left(next, units) else {
+                    continue;
+                };
+                if best.get(&next).is_some_and(|&c| c <= cost + 1) {
+                    continue;
+                }
+                best.insert(next, cost + 1);
+                previous.insert(next, (state, Command { command_type, unit }));
+                queue.push(Reverse((cost + 1 + left, Reverse(cost + 1), next)));
+            }
+        }
+    }
+    None
+}
+
+// Print a program reaching `target`, as a day 2 input
+pub fn print_plan(target: Point<i64>, units: UnitBounds) -> util::Result<()> {
+    let commands = plan(target, units).ok_or_else(|| {
+        format!(
+            "no plan reaches {} with units {}..{}",
+            target, units.min, units.max
+        )
+    })?;
+    for command in commands {
+        println!("{}", command);
+    }
+    Ok(())
+}
+
+struct Submarine {
+    name: String,
+    program: Vec<Statement>,
+}
+
+// Programs of several submarines, either after a `[name]` line or on lines
+// starting with `name:`. Each program is parsed on its own with the lines of
+// the others blanked out, so errors point into the whole file.
+fn parse_fleet(source: &str) -> Result<Vec<Submarine>, ParseError> {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut names: Vec<String> = Vec::new();
+    let mut sources: Vec<Vec<String>> = Vec::new();
+    let mut section = None;
+    for (i, line) in lines.iter().enumerate() {
+        let code = line.split('#').next().unwrap().trim();
+        if code.is_empty() {
+            continue;
+        }
+        let mut owner = |name: &str| match names.iter().position(|n| n == name) {
+            Some(index) => index,
+            None => {
+                names.push(name.to_string());
+                sources.push(vec![String::new(); lines.len()]);
+                names.len() - 1
+            }
+        };
+        if let Some(name) = code.strip_prefix('[').and_then(|c| c.strip_suffix(']')) {
+            section = Some(owner(name.trim()));
+            continue;
+        }
+        let prefix = line
+            .split_once(':')
+            .filter(|(name, _)| !name.trim().is_empty() && !name.trim().contains(' '));
+        let (sub, text) = match (prefix, section) {
+            // Spaces instead of the name keep the columns right
+            (Some((name, rest)), _) => (
+                owner(name.trim()),
+                format!("{} {}", " ".repeat(name.len()), rest),
+            ),
+            (None, Some(sub)) => (sub, line.to_string()),
+            (None, None) => {
+                let column = line.len() - line.trim_start().len() + 1;
+                return error(
+                    Location {
+                        line: i + 1,
+                        column,
+                    },
+                    "command before any submarine",
+                );
+            }
+        };
+        sources[sub][i] = text;
+    }
+    names
+        .into_iter()
+        .zip(sources)
+        .map(|(name, lines)| {
+            Ok(Submarine {
+                name,
+                program: parse_program(&lines.join("\n"))?,
+            })
+        })
+        .collect()
+}
+
+// Two submarines closer than the safety distance after a step, a distance
+// of 0 is a collision
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Encounter {
+    step: usize,
+    subs: (usize, usize),
+    position: Point<i64>,
+    distance: i64,
+}
+
+// Run all the programs with the aim model, one command per submarine and
+// step. Submarines that are done stay where they are. Two submarines that
+// stay close without moving are only reported on the first step. Returns the
+// encounters and where each submarine ended, or an error once the programs
+// ran for `limit` steps.
+fn simulate_fleet(
+    fleet: &[Submarine],
+    safety: i64,
+    limit: usize,
+) -> util::Result<(Vec<Encounter>, Vec<Point<i64>>)> {
+    let mut cursors: Vec<Cursor> = fleet.iter().map(|sub| Cursor::new(&sub.program)).collect();
+    let mut models: Vec<NewPosition> = fleet.iter().map(|_| NewPosition::default()).collect();
+    let position = |model: &NewPosition| Point::from(model.position());
+    let mut encounters = Vec::new();
+    // Pairs that were close on the previous step
+    let mut close = vec![vec![false; fleet.len()]; fleet.len()];
+    let mut moved = vec![false; fleet.len()];
+    for step in 1.. {
+        let mut running = false;
+        moved.fill(false);
+        for (i, (model, cursor)) in models.iter_mut().zip(&mut cursors).enumerate() {
+            if let Some(command) = cursor.next() {
+                let before = model.position();
+                model.apply(command);
+                moved[i] = model.position() != before;
+                running = true;
+            }
+        }
+        if !running {
+            break;
+        }
+        if step > limit {
+            return Err(format!("the fleet is still running after {} steps", limit).into());
+        }
+        for a in 0..models.len() {
+            for b in a + 1..models.len() {
+                let distance = position(&models[a]).manhattan(position(&models[b]));
+                let was_close = std::mem::replace(&mut close[a][b], distance <= safety);
+                if distance <= safety && !(was_close && !moved[a] && !moved[b]) {
+                    encounters.push(Encounter {
+                        step,
+                        subs: (a, b),
+                        position: position(&models[a]),
+                        distance,
+                    });
+                }
+            }
+        }
+    }
+    Ok((encounters, models.iter().map(position).collect()))
+}
+
+// Report the submarines of the fleet in `path` coming within `safety` of
+// each other, by Manhattan distance
+pub fn fleet(path: &Path, safety: i64) -> util::Result<()> {
+    let fleet = parse_fleet(&fs::read_to_string(path)?)?;
+    let (encounters, positions) = simulate_fleet(&fleet, safety, FLEET_STEP_LIMIT)?;
+    for e in &encounters {
+        let (a, b) = (&fleet[e.subs.0].name, &fleet[e.subs.1].name);
+        if e.distance == 0 {
+            println!("step {}: {} and {} collide at {}", e.step, a, b, e.position);
+        } else {
+            println!("step {}: {} and {} are {} apart", e.step, a, b, e.distance);
+        }
+    }
+    for (sub, position) in fleet.iter().zip(positions) {
+        println!("{} ends at {}", sub.name, position);
+    }
+    Ok(())
+}
+
+// Same answers as `day_02`, applying each statement as soon as it is read.
+// Only the lines of the statement being read are kept, however long the
+// input.
+pub fn day_02_streaming(reader: impl BufRead) -> util::Result<()> {
+    let mut models = select_models(&PUZZLE_MODELS)?;
+    navigate(reader, &mut models)?;
+    print_positions(&models);
+    Ok(())
+}
+
+fn navigate(
+    reader: impl BufRead,
+    models: &mut [(&str, Box<dyn NavigationModel>)],
+) -> util::Result<()> {
+    // Lines read since the last complete statements, from line `first` on
+    let mut pending = String::new();
+    let mut first = 0;
+    let mut depth = 0;
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if pending.is_empty() {
+            first = i;
+        }
+        let code = line.split('#').next().unwrap();
+        depth += code.matches('{').count() as i64 - code.matches('}').count() as i64;
+        pending.push_str(&line);
+        pending.push('\n');
+        // Nothing can be complete inside a block, don't parse it again and
+        // again while it grows
+        if depth > 0 {
+            continue;
+        }
+        match parse_program(&pending) {
+            Ok(program) => {
+                evaluate(&program, models);
+            }
+            Err(e) if e.incomplete => continue,
+            Err(e) => return Err(e.shifted(first).into()),
+        }
+        pending.clear();
+    }
+    parse_program(&pending).map_err(|e| e.shifted(first))?;
+    Ok(())
+}
+
+#[test]
+fn navigate_small_example() {
+    let contents = include_str!("../resources/day02_small.txt");
+    let mut models = select_models(&["simple", "aim", "capped"]).unwrap();
+    models.push((
+        "strong drag",
+        Box::new(AimWithDrag {
+            aim: NewPosition::default(),
+            percent: 50,
+        }),
+    ));
+    navigate(contents.as_bytes(), &mut models).unwrap();
+    let positions: Vec<(i64, i64)> = models.iter().map(|(_, m)| m.position()).collect();
+    assert_eq!(positions, vec![(15, 10), (15, 60), (15, 30), (15, 56)]);
+    assert!(select_models(&["sideways"]).is_err());
+}
+
+#[test]
+fn capped_descent_keeps_the_aim_past_the_cap() {
+    let program = parse_program("down 5\nup 3\nforward 1\ndown 4\nforward 2\n").unwrap();
+    let mut models = select_models(&["aim", "capped"]).unwrap();
+    evaluate(&program, &mut models);
+    // The aim goes up to 5 then back to 2, under the cap of 3
+    let (_, aim) = &models[0];
+    let (_, capped) = &models[1];
+    assert_eq!(aim.position(), (3, 2 + 12));
+    assert_eq!(capped.position(), (3, 2 + 6));
+    assert_eq!(capped.aim(), Some(6));
+}
+
+#[test]
+fn program_with_repeat_blocks() {
+    let program = parse_program(
+        "# warm up\nforward 5\nrepeat 2 {\n  down 3 # dive\n  repeat 3 { forward 1 }\n}\nhold\nback 2\ndive-to 4\n",
+    )
+    .unwrap();
     let mut pos = Position { x: 0, y: 0 };
     let mut new_pos = NewPosition {
         depth: 0,
         hori_pos: 0,
         aim: 0,
     };
+    let mut count = 0;
+    interpret(&program, &mut |command| {
+        pos.apply(command);
+        new_pos.apply(command);
+        count += 1;
+    });
+    assert_eq!(count, 12);
+    assert_eq!(Cursor::new(&program).count(), 12);
+    assert_eq!((pos.x, pos.y), (9, 4));
+    assert_eq!((new_pos.hori_pos, new_pos.depth, new_pos.aim), (9, 4, 6));
+}
 
-    for command in commands.iter() {
-        match command.command_type {
-            CommandType::Forward => pos.x += command.unit,
-            CommandType::Up => pos.y -= command.unit,
-            CommandType::Down => pos.y += command.unit,
+#[test]
+fn parse_errors_have_locations() {
+    let location = |source| parse_program(source).unwrap_err().location;
+    assert_eq!(
+        location("forward 5\nsideways 2"),
+        Location { line: 2, column: 1 }
+    );
+    assert_eq!(
+        location("forward 5\n  up x"),
+        Location { line: 2, column: 6 }
+    );
+    assert_eq!(
+        location("repeat 2 {\n down 1\n"),
+        Location {
+            line: 1,
+            column: 10
         }
-        match command.command_type {
-            CommandType::Down => new_pos.aim += command.unit,
-            CommandType::Up => new_pos.aim -= command.unit,
-            CommandType::Forward => {
-                new_pos.hori_pos += command.unit;
-                new_pos.depth += new_pos.aim * command.unit;
+    );
+    assert_eq!(location("down 1 }"), Location { line: 1, column: 8 });
+    assert_eq!(location("down"), Location { line: 1, column: 1 });
+    assert_eq!(
+        parse_program("up 1\nforward 2 ; down 3")
+            .unwrap_err()
+            .to_string(),
+        "line 2, column 11: unexpected character ';'"
+    );
+}
+
+#[test]
+fn streaming_reads_statements_across_lines() {
+    let source = "forward 5\nrepeat 2 { # lines of a block\n  down 3\n  repeat 3 {\n    forward 1\n  }\n}\nrepeat 2\n{ forward\n2 }\n";
+    let mut streamed = select_models(&PUZZLE_MODELS).unwrap();
+    navigate(source.as_bytes(), &mut streamed).unwrap();
+    let mut whole = select_models(&PUZZLE_MODELS).unwrap();
+    evaluate(&parse_program(source).unwrap(), &mut whole);
+    let positions = |models: &[(&str, Box<dyn NavigationModel>)]| {
+        models.iter().map(|(_, m)| m.position()).collect::<Vec<_>>()
+    };
+    assert_eq!(positions(&streamed), vec![(15, 6), (15, 51)]);
+    assert_eq!(positions(&streamed), positions(&whole));
+
+    let error = |source: &str| {
+        let mut models = select_models(&PUZZLE_MODELS).unwrap();
+        navigate(source.as_bytes(), &mut models)
+            .unwrap_err()
+            .to_string()
+    };
+    assert_eq!(
+        error("forward 1\nrepeat 2 {\n  down x\n}\n"),
+        "line 3, column 8: expected a number, found \"x\""
+    );
+    assert_eq!(
+        error("forward 1\nrepeat 2 {\n  down 1\n"),
+        "line 2, column 10: unclosed {"
+    );
+    assert_eq!(
+        error("forward 1\n}\n"),
+        "line 2, column 1: expected a command, found }"
+    );
+}
+
+#[test]
+fn record_trajectories() {
+    let contents = include_str!("../resources/day02_small.txt");
+    let program = parse_program(contents).unwrap();
+    let mut models = select_models(&PUZZLE_MODELS).unwrap();
+    let trajectories = record(&program, &mut models);
+    assert_eq!(trajectories[0].samples.len(), 7);
+    assert_eq!(trajectories[0].max_depth(), (5, 10));
+    assert_eq!(trajectories[1].max_depth(), (6, 60));
+    let mut models = select_models(&PUZZLE_MODELS).unwrap();
+    assert_eq!(evaluate(&program, &mut models), vec![(5, 10), (6, 60)]);
+
+    let csv = trajectories[1].to_csv();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], "step,position,depth,aim");
+    assert_eq!(lines[4], "3,13,40,5");
+    assert_eq!(trajectories[0].to_csv().lines().nth(3), Some("2,5,5,"));
+    let svg = trajectories[1].to_svg();
+    assert!(svg.contains("viewBox=\"0 0 15 60\""));
+    assert!(svg.contains("points=\"0,0 5,0 5,0 13,40 13,40 13,40 15,60\""));
+}
+
+#[test]
+fn plans_are_shortest_and_round_trip() {
+    use std::collections::{HashSet, VecDeque};
+
+    let units = UnitBounds { min: 1, max: 3 };
+    // Shortest program length for every target, found by trying all of them
+    let mut shortest = HashMap::new();
+    let mut seen = HashSet::from([(0, 0, 0)]);
+    let mut queue = VecDeque::from([((0i64, 0i64, 0i64), 0)]);
+    while let Some(((h, depth, aim), len)) = queue.pop_front() {
+        shortest.entry((h, depth)).or_insert(len);
+        for u in 1..=3 {
+            for next in [
+                (h + u, depth + aim * u, aim),
+                (h, depth, aim + u),
+                (h, depth, aim - u),
+            ] {
+                if next.0 <= 6 && next.1.abs() <= 60 && next.2.abs() <= 20 && seen.insert(next) {
+                    queue.push_back((next, len + 1));
+                }
             }
         }
     }
+    for h in 0..=6 {
+        for depth in -15..=15 {
+            let target = Point::new(h, depth);
+            let Some(commands) = plan(target, units) else {
+                assert!(!shortest.contains_key(&(h, depth)), "missed {}", target);
+                continue;
+            };
+            assert_eq!(
+                Some(&commands.len()),
+                shortest.get(&(h, depth)),
+                "{}",
+                target
+            );
+            let source: String = commands.iter().map(|c| format!("{}\n", c)).collect();
+            let mut aim = NewPosition::default();
+            interpret(&parse_program(&source).unwrap(), &mut |c| aim.apply(c));
+            assert_eq!(aim.position(), (h, depth));
+        }
+    }
+    assert_eq!(plan(Point::new(0, 5), units), None);
+    // No sum of 2 and 3 makes 1, and no sum of 3 and 4 makes 5
+    assert_eq!(plan(Point::new(1, 5), UnitBounds { min: 2, max: 3 }), None);
+    assert_eq!(plan(Point::new(5, 0), UnitBounds { min: 3, max: 4 }), None);
+    assert!(plan(Point::new(6, 0), UnitBounds { min: 3, max: 4 }).is_some());
+    assert!(UnitBounds::parse("3..1").is_err());
+}
 
-    println!(
-        "Final depth depth calculated with simple method {}",
-        pos.x * pos.y
+#[test]
+fn fleet_in_lockstep() {
+    let fleet = parse_fleet(
+        "[alpha]\ndown 1\nforward 4\nbravo: forward 2 # sections and prefixes mix\n\nbravo: down 2\nforward 1\n\n[charlie]\nrepeat 3 { hold }\n",
+    )
+    .unwrap();
+    let names: Vec<&str> = fleet.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, vec!["alpha", "bravo", "charlie"]);
+    // Alpha only aims on its first step, staying with charlie which holds
+    let (encounters, positions) = simulate_fleet(&fleet, 0, 10).unwrap();
+    assert_eq!(
+        encounters,
+        vec![Encounter {
+            step: 1,
+            subs: (0, 2),
+            position: Point::new(0, 0),
+            distance: 0,
+        }]
     );
-    println!(
-        "Final depth depth calculated with complex method {}",
-        new_pos.depth * new_pos.hori_pos
+    // Bravo stops 2 away from charlie after step 1, and is 2 away from alpha.
+    // Bravo and charlie don't move after that, so they are reported once.
+    let steps: Vec<usize> = simulate_fleet(&fleet, 2, 10)
+        .unwrap()
+        .0
+        .iter()
+        .map(|e| e.step)
+        .collect();
+    assert_eq!(steps, vec![1, 1, 1]);
+    assert!(simulate_fleet(&fleet, 2, 2).is_err());
+    assert_eq!(
+        positions,
+        vec![Point::new(5, 5), Point::new(2, 0), Point::new(0, 0)]
     );
-    return Ok(());
-}
 
-fn parse_command(com_str: &str) -> Result<Command, &'static str> {
-    let (command, unit) = com_str.split_once(' ').unwrap();
-
-    let command = match command {
-        "forward" => CommandType::Forward,
-        "up" => CommandType::Up,
-        "down" => CommandType::Down,
-        _ => return Err("Wrong command"),
-    };
+    let error = parse_fleet("[alpha]\nforward 1\nbravo: up one\n")
+        .err()
+        .unwrap();
+    assert_eq!(
+        error.location,
+        Location {
+            line: 3,
+            column: 11
+        }
+    );
+    assert!(parse_fleet("forward 1\n").is_err());
 
-    Ok(Command {
-        command_type: command,
-        unit: unit.parse::<i32>().unwrap(),
-    })
+    // Programs are stepped through, not expanded
+    let fleet = parse_fleet(
+        "a: repeat 1000000000 { repeat 1000000000 { hold } }\nb: repeat 0 { down 1 } forward 1\n",
+    )
+    .unwrap();
+    let mut cursor = Cursor::new(&fleet[0].program);
+    assert_eq!(
+        cursor.nth(5).map(|c| c.command_type),
+        Some(CommandType::Hold)
+    );
+    let commands: Vec<String> = Cursor::new(&fleet[1].program)
+        .map(|c| c.to_string())
+        .collect();
+    assert_eq!(commands, vec!["forward 1"]);
+    assert_eq!(
+        simulate_fleet(&fleet, 0, 1000).unwrap_err().to_string(),
+        "the fleet is still running after 1000 steps"
+    );
 }
@@ -1,115 +1,558 @@
-use std::fs::File;
-use std::io::prelude::*;
+use crate::explain::Trace;
+use crate::util;
+use std::fmt;
 
-type Bit2DArray = Vec<Vec<u32>>;
+// Rating values have to fit in a u64
+const MAX_WIDTH: usize = 64;
+// Surviving rows listed in explanations, past that only their count is
+const LISTED_ROWS: usize = 8;
 
-pub fn day_03() -> std::io::Result<()> {
-    //let mut file = File::open("./resources/day03_small.txt")?;
-    let mut file = File::open("./resources/day03.txt")?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
+// Set of row indices, 64 to a word
+#[derive(Debug, Clone, PartialEq)]
+struct RowSet {
+    words: Vec<u64>,
+}
 
-    println!("contents:\n{}", contents);
+impl RowSet {
+    fn empty(len: usize) -> Self {
+        RowSet {
+            words: vec![0; len.div_ceil(64)],
+        }
+    }
+
+    fn full(len: usize) -> Self {
+        let mut set = Self::empty(len);
+        for row in 0..len {
+            set.insert(row);
+        }
+        set
+    }
+
+    fn insert(&mut self, row: usize) {
+        self.words[row / 64] |= 1 << (row % 64);
+    }
+
+    fn len(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    // Number of rows in both sets
+    fn count_common(&self, other: &Self) -> usize {
+        self.words
+            .iter()
+            .zip(&other.words)
+            .map(|(a, b)| (a & b).count_ones() as usize)
+            .sum()
+    }
+
+    // Keep the rows that are in `other`, or the ones that aren't
+    fn retain(&mut self, other: &Self, keep: bool) {
+        for (a, b) in self.words.iter_mut().zip(&other.words) {
+            *a &= if keep { *b } else { !*b };
+        }
+    }
 
-    let bit_2d_array: Bit2DArray = contents
-        .trim()
-        .split('\n')
-        .map(|line| {
-            line.trim()
-                .chars()
-                .map(|c| c.to_digit(2u32).unwrap())
-                .collect()
+    fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(i, &word)| {
+            (0..64)
+                .filter(move |bit| word >> bit & 1 == 1)
+                .map(move |bit| i * 64 + bit)
         })
-        .collect();
+    }
 
-    let col_size = bit_2d_array[0].len();
-    //println!(" parse content: {:?}", bit_2d_array);
-    println!(" parse content: {:?}", bit_2d_array[0]);
-
-    let gamma_rate = (0..col_size)
-        .map(|col| most_common_bit(&bit_2d_array, col))
-        .collect::<Vec<u32>>();
-    let epsilon_rate = (0..col_size)
-        .map(|col| least_common_bit(&bit_2d_array, col))
-        .collect::<Vec<u32>>();
-
-    println!("gamma_rate {:?}", gamma_rate);
-    println!("epsilon_rate {:?}", epsilon_rate);
-
-    let gamma_rate = u32::from_str_radix(convert_into_binary_str(gamma_rate).as_str(), 2).unwrap();
-    let epsilon_rate =
-        u32::from_str_radix(convert_into_binary_str(epsilon_rate).as_str(), 2).unwrap();
-    println!("Power consumption (part 1): {}", gamma_rate * epsilon_rate);
-
-    let mut filtered_most_common = bit_2d_array.clone();
-    let mut filtered_least_common = bit_2d_array.clone();
-
-    for col in 0..col_size {
-        let most_common = most_common_bit(&filtered_most_common, col);
-        filtered_most_common =
-            filter_array_with_bit_at_col(&filtered_most_common, most_common, col);
-        if filtered_most_common.len() == 1 {
-            break;
+    fn first(&self) -> Option<usize> {
+        self.words
+            .iter()
+            .position(|&w| w != 0)
+            .map(|i| i * 64 + self.words[i].trailing_zeros() as usize)
+    }
+}
+
+// The diagnostic report stored by column: the set of a column holds the rows
+// with a 1 there. Counting the ones of the rows still in play is then a
+// popcount, and filtering rows never copies them.
+#[derive(Debug)]
+struct Report {
+    rows: usize,
+    columns: Vec<RowSet>,
+}
+
+impl Report {
+    fn width(&self) -> usize {
+        self.columns.len()
+    }
+
+    fn all_rows(&self) -> RowSet {
+        RowSet::full(self.rows)
+    }
+
+    fn bit(&self, row: usize, col: usize) -> u32 {
+        let set = &self.columns[col];
+        ((set.words[row / 64] >> (row % 64)) & 1) as u32
+    }
+
+    fn row_string(&self, row: usize) -> String {
+        (0..self.width())
+            .map(|col| if self.bit(row, col) == 0 { '0' } else { '1' })
+            .collect()
+    }
+
+    fn row_value(&self, row: usize) -> u64 {
+        (0..self.width()).fold(0, |value, col| value << 1 | u64::from(self.bit(row, col)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Rate {
+    // Made of the most common bits
+    Gamma,
+    // Made of the least common bits
+    Epsilon,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Rating {
+    // Keeps the rows with the most common bit
+    OxygenGenerator,
+    // Keeps the rows with the least common bit
+    Co2Scrubber,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Selection {
+    MostCommon,
+    LeastCommon,
+}
+
+// Bit kept when a column has as many 0s as 1s
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TiePolicy {
+    PreferZero,
+    PreferOne,
+    // Not used by the puzzle's ratings
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ColumnOrder {
+    LeftToRight,
+    RightToLeft,
+}
+
+// How a rating picks the bit rows must have, one column after the other
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Criteria {
+    name: &'static str,
+    selection: Selection,
+    ties: TiePolicy,
+    order: ColumnOrder,
+}
+
+impl Rating {
+    fn criteria(self) -> Criteria {
+        match self {
+            Rating::OxygenGenerator => Criteria {
+                name: "oxygen generator",
+                selection: Selection::MostCommon,
+                ties: TiePolicy::PreferOne,
+                order: ColumnOrder::LeftToRight,
+            },
+            Rating::Co2Scrubber => Criteria {
+                name: "CO2 scrubber",
+                selection: Selection::LeastCommon,
+                ties: TiePolicy::PreferZero,
+                order: ColumnOrder::LeftToRight,
+            },
         }
     }
-    for col in 0..col_size {
-        let least_common = least_common_bit(&filtered_least_common, col);
-        filtered_least_common =
-            filter_array_with_bit_at_col(&filtered_least_common, least_common, col);
-        if filtered_least_common.len() == 1 {
-            break;
+}
+
+impl Rate {
+    fn criteria(self) -> Criteria {
+        match self {
+            Rate::Gamma => Criteria {
+                name: "gamma",
+                selection: Selection::MostCommon,
+                ties: TiePolicy::PreferOne,
+                order: ColumnOrder::LeftToRight,
+            },
+            Rate::Epsilon => Criteria {
+                name: "epsilon",
+                selection: Selection::LeastCommon,
+                ties: TiePolicy::PreferZero,
+                order: ColumnOrder::LeftToRight,
+            },
         }
     }
+}
 
-    println!("filtered_most_common: {:?}", filtered_most_common);
-    let oxygen_gen_rate = u32::from_str_radix(
-        convert_into_binary_str(filtered_most_common[0].clone()).as_str(),
-        2,
-    )
-    .unwrap();
+impl Criteria {
+    // Parse `selection,ties[,order]`: `most` or `least`, then `zero`, `one`
+    // or `error`, then `ltr` (the default) or `rtl`
+    pub(crate) fn parse(s: &str) -> util::Result<Self> {
+        let parts: Vec<&str> = s.split(',').collect();
+        let (selection, ties, order) = match parts.as_slice() {
+            [selection, ties] => (selection, ties, &"ltr"),
+            [selection, ties, order] => (selection, ties, order),
+            _ => return Err(format!("expected selection,ties[,order]: {}", s).into()),
+        };
+        Ok(Criteria {
+            name: "custom",
+            selection: match *selection {
+                "most" => Selection::MostCommon,
+                "least" => Selection::LeastCommon,
+                _ => return Err(format!("unknown selection {}", selection).into()),
+            },
+            ties: match *ties {
+                "zero" => TiePolicy::PreferZero,
+                "one" => TiePolicy::PreferOne,
+                "error" => TiePolicy::Error,
+                _ => return Err(format!("unknown tie policy {}", ties).into()),
+            },
+            order: match *order {
+                "ltr" => ColumnOrder::LeftToRight,
+                "rtl" => ColumnOrder::RightToLeft,
+                _ => return Err(format!("unknown column order {}", order).into()),
+            },
+        })
+    }
+
+    // Bit selected in a column where `ones` of the `rows` have a 1, `None` on
+    // a tie that is an error
+    fn select(&self, ones: usize, rows: usize) -> Option<u32> {
+        let zeros = rows - ones;
+        if ones == zeros {
+            return match self.ties {
+                TiePolicy::PreferZero => Some(0),
+                TiePolicy::PreferOne => Some(1),
+                TiePolicy::Error => None,
+            };
+        }
+        let most_common = u32::from(ones > zeros);
+        Some(match self.selection {
+            Selection::MostCommon => most_common,
+            Selection::LeastCommon => 1 - most_common,
+        })
+    }
+
+    fn tie(&self, col: usize) -> String {
+        format!("{}: tie in column {}", self.name, col)
+    }
+
+    fn columns(&self, width: usize) -> Vec<usize> {
+        match self.order {
+            ColumnOrder::LeftToRight => (0..width).collect(),
+            ColumnOrder::RightToLeft => (0..width).rev().collect(),
+        }
+    }
+
+    // Value made of the bit selected in each column, among all the rows
+    fn rate(&self, report: &Report) -> util::Result<u64> {
+        let mut value = 0;
+        for col in 0..report.width() {
+            let ones = report.columns[col].len();
+            let bit = self
+                .select(ones, report.rows)
+                .ok_or_else(|| self.tie(col))?;
+            value |= u64::from(bit) << (report.width() - 1 - col);
+        }
+        Ok(value)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Event {
+    Rates {
+        gamma: u64,
+        epsilon: u64,
+    },
+    Column {
+        rating: &'static str,
+        col: usize,
+        bit: u32,
+        // The rows still in play after filtering on this column
+        survivors: Vec<String>,
+    },
+    Found {
+        rating: &'static str,
+        row: String,
+        value: u64,
+    },
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::Rates { gamma, epsilon } => write!(
+                f,
+                "gamma rate {} and epsilon rate {} give a power consumption of {}",
+                gamma,
+                epsilon,
+                u128::from(*gamma) * u128::from(*epsilon)
+            ),
+            Event::Column {
+                rating,
+                col,
+                bit,
+                survivors,
+            } => {
+                write!(
+                    f,
+                    "{}: column {} keeps rows with a {}, {} left",
+                    rating,
+                    col,
+                    bit,
+                    survivors.len()
+                )?;
+                if survivors.len() <= LISTED_ROWS {
+                    write!(f, ": {}", survivors.join(" "))?;
+                }
+                Ok(())
+            }
+            Event::Found { rating, row, value } => {
+                write!(f, "{}: rating is {} = {}", rating, row, value)
+            }
+        }
+    }
+}
+
+pub fn day_03() -> util::Result<()> {
+    //let contents = util::read_input("day03_small.txt")?;
+    let contents = util::read_input("day03.txt")?;
+
+    println!("contents:\n{}", contents);
+
+    let report = parse(&contents)?;
+
+    println!(" parse content: {}", report.row_string(0));
+
+    let gamma_rate = Rate::Gamma.criteria().rate(&report)?;
+    let epsilon_rate = Rate::Epsilon.criteria().rate(&report)?;
+
+    println!("gamma_rate {:0w$b}", gamma_rate, w = report.width());
+    println!("epsilon_rate {:0w$b}", epsilon_rate, w = report.width());
+
+    println!(
+        "Power consumption (part 1): {}",
+        u128::from(gamma_rate) * u128::from(epsilon_rate)
+    );
+
+    let mut trace = Trace::disabled();
+    let oxygen_gen_rate = find_rating(&report, Rating::OxygenGenerator, &mut trace)?;
     println!("oxygen_gen_rate: {:?}", oxygen_gen_rate);
-    let co2_rate = u32::from_str_radix(
-        convert_into_binary_str(filtered_least_common[0].clone()).as_str(),
-        2,
-    )
-    .unwrap();
+    let co2_rate = find_rating(&report, Rating::Co2Scrubber, &mut trace)?;
     println!("co2_rate: {:?}", co2_rate);
     println!(
         "Life support rating (part 2): {}",
-        oxygen_gen_rate * co2_rate
+        u128::from(oxygen_gen_rate) * u128::from(co2_rate)
     );
 
     Ok(())
 }
 
-fn most_common_bit(bit_2d_array: &Bit2DArray, col: usize) -> u32 {
-    let bit0_count = bit_2d_array.iter().filter(|row| row[col] == 0).count();
-    let bit1_count = bit_2d_array.iter().filter(|row| row[col] == 1).count();
-    if bit0_count > bit1_count {
-        return 0;
+fn parse(contents: &str) -> util::Result<Report> {
+    let lines: Vec<&str> = contents.trim().lines().map(str::trim).collect();
+    let width = lines.first().ok_or("empty report")?.len();
+    if width > MAX_WIDTH {
+        return Err(format!(
+            "rows of {} bits, at most {} are supported",
+            width, MAX_WIDTH
+        )
+        .into());
     }
-    return 1;
+    let mut columns = vec![RowSet::empty(lines.len()); width];
+    for (row, line) in lines.iter().enumerate() {
+        if line.len() != width {
+            return Err(format!("line {}: expected {} bits", row + 1, width).into());
+        }
+        for (col, c) in line.chars().enumerate() {
+            match c {
+                '0' => (),
+                '1' => columns[col].insert(row),
+                _ => return Err(format!("line {}: not a bit: {:?}", row + 1, c).into()),
+            }
+        }
+    }
+    Ok(Report {
+        rows: lines.len(),
+        columns,
+    })
+}
+
+pub fn explain() -> util::Result<()> {
+    let contents = util::read_input("day03.txt")?;
+    let report = parse(&contents)?;
+
+    let mut trace = Trace::new();
+    trace.push(Event::Rates {
+        gamma: Rate::Gamma.criteria().rate(&report)?,
+        epsilon: Rate::Epsilon.criteria().rate(&report)?,
+    });
+    find_rating(&report, Rating::OxygenGenerator, &mut trace)?;
+    find_rating(&report, Rating::Co2Scrubber, &mut trace)?;
+    print!("{}", trace);
+    Ok(())
 }
 
-fn least_common_bit(bit_2d_array: &Bit2DArray, col: usize) -> u32 {
-    if most_common_bit(bit_2d_array, col) == 0 {
-        1
+// Rating of the report with criteria given on the command line, listing the
+// columns when `explain` is set
+pub fn custom_rating(criteria: &Criteria, explain: bool) -> util::Result<()> {
+    let contents = util::read_input("day03.txt")?;
+    let report = parse(&contents)?;
+    let mut trace = if explain {
+        Trace::new()
     } else {
-        0
-    }
+        Trace::disabled()
+    };
+    let rating = rate_rows(&report, criteria, &mut trace);
+    print!("{}", trace);
+    println!("{} rating: {}", criteria.name, rating?);
+    Ok(())
 }
 
-fn convert_into_binary_str(v: Vec<u32>) -> String {
-    v.iter()
-        .map(|d| if *d == 0u32 { '0' } else { '1' })
-        .collect()
+// Filter the rows column by column on the bit selected by the rating, until
+// only one row is left
+fn find_rating(report: &Report, rating: Rating, trace: &mut Trace<Event>) -> util::Result<u64> {
+    rate_rows(report, &rating.criteria(), trace)
 }
 
-fn filter_array_with_bit_at_col(bit_2d_array: &Bit2DArray, bit: u32, col: usize) -> Bit2DArray {
-    let new_2d_array: Bit2DArray = bit_2d_array
+// Filter the rows on the bit the criteria select, one column at a time, until
+// only one row is left. A column where all the rows left have the same bit
+// keeps them all, so identical rows are never told apart: the first one is
+// taken.
+fn rate_rows(report: &Report, criteria: &Criteria, trace: &mut Trace<Event>) -> util::Result<u64> {
+    let mut filtered = report.all_rows();
+    for col in criteria.columns(report.width()) {
+        let ones = report.columns[col].count_common(&filtered);
+        let bit = if ones == 0 || ones == filtered.len() {
+            u32::from(ones > 0)
+        } else {
+            criteria
+                .select(ones, filtered.len())
+                .ok_or_else(|| criteria.tie(col))?
+        };
+        filtered.retain(&report.columns[col], bit == 1);
+        trace.push_with(|| Event::Column {
+            rating: criteria.name,
+            col,
+            bit,
+            survivors: filtered.iter().map(|row| report.row_string(row)).collect(),
+        });
+        if filtered.len() == 1 {
+            break;
+        }
+    }
+
+    let first = filtered
+        .first()
+        .ok_or_else(|| format!("{}: no rows left", criteria.name))?;
+    let value = report.row_value(first);
+    trace.push_with(|| Event::Found {
+        rating: criteria.name,
+        row: report.row_string(first),
+        value,
+    });
+    Ok(value)
+}
+
+#[test]
+fn find_rating_small_example() {
+    let report = parse(include_str!("../resources/day03_small.txt")).unwrap();
+    let mut trace = Trace::new();
+    assert_eq!(
+        find_rating(&report, Rating::OxygenGenerator, &mut trace).unwrap(),
+        23
+    );
+    let remaining: Vec<usize> = trace
+        .events()
         .iter()
-        .filter(|row| row[col] == bit)
-        .map(|row| row.clone()) // TODO: how to avoid clone?
+        .filter_map(|e| match e {
+            Event::Column { survivors, .. } => Some(survivors.len()),
+            _ => None,
+        })
         .collect();
-    return new_2d_array;
+    assert_eq!(remaining, vec![7, 4, 3, 2, 1]);
+    assert_eq!(
+        find_rating(&report, Rating::Co2Scrubber, &mut Trace::disabled()).unwrap(),
+        10
+    );
+    assert_eq!(Rate::Gamma.criteria().rate(&report).unwrap(), 22);
+    assert_eq!(Rate::Epsilon.criteria().rate(&report).unwrap(), 9);
+}
+
+#[test]
+fn identical_rows_keep_a_rating() {
+    // Both ratings keep the two rows, on every column they agree
+    let report = parse("10\n10\n").unwrap();
+    for rating in [Rating::OxygenGenerator, Rating::Co2Scrubber] {
+        let mut trace = Trace::new();
+        assert_eq!(find_rating(&report, rating, &mut trace).unwrap(), 0b10);
+        assert!(trace.events().iter().all(|e| match e {
+            Event::Column { survivors, .. } => survivors.len() == 2,
+            _ => true,
+        }));
+    }
+    // The least common bit of a column of ones is still 0
+    assert_eq!(Rate::Epsilon.criteria().rate(&report).unwrap(), 0b01);
+}
+
+#[test]
+fn wide_reports() {
+    let report = parse(&format!(
+        "{}\n{}\n{}\n",
+        "1".repeat(40),
+        "0".repeat(40),
+        "10".repeat(20)
+    ))
+    .unwrap();
+    assert_eq!(
+        Rate::Gamma.criteria().rate(&report).unwrap(),
+        0xAA_AAAA_AAAA
+    );
+    assert_eq!(report.row_value(0), (1 << 40) - 1);
+    assert_eq!(
+        find_rating(&report, Rating::Co2Scrubber, &mut Trace::disabled()).unwrap(),
+        0
+    );
+    assert!(parse(&"1".repeat(65)).is_err());
+    assert!(parse("101\n10\n").is_err());
+}
+
+#[test]
+fn custom_criteria() {
+    let report = parse(include_str!("../resources/day03_small.txt")).unwrap();
+    let mut criteria = Criteria::parse("most,error,rtl").unwrap();
+    assert_eq!(
+        criteria,
+        Criteria {
+            name: "custom",
+            selection: Selection::MostCommon,
+            ties: TiePolicy::Error,
+            order: ColumnOrder::RightToLeft,
+        }
+    );
+    assert_eq!(
+        Criteria::parse("least,one").unwrap().order,
+        ColumnOrder::LeftToRight
+    );
+    assert!(Criteria::parse("most").is_err());
+    assert!(Criteria::parse("most,maybe").is_err());
+    // The last column has 5 ones out of 12 rows, the next one 4 out of the 7
+    // left, and then there is a tie between 2 and 2
+    let mut trace = Trace::new();
+    assert!(rate_rows(&report, &criteria, &mut trace).is_err());
+    match &trace.events()[1] {
+        Event::Column { col, survivors, .. } => {
+            assert_eq!(*col, 3);
+            assert_eq!(survivors, &["11110", "10110", "00010", "01010"]);
+        }
+        event => panic!("unexpected event {:?}", event),
+    }
+    assert_eq!(trace.events().len(), 2);
+
+    criteria.ties = TiePolicy::PreferZero;
+    assert_eq!(
+        rate_rows(&report, &criteria, &mut Trace::disabled()).unwrap(),
+        0b00010
+    );
 }
@@ -1,6 +1,6 @@
+use crate::explain::Trace;
+use crate::util;
 use std::fmt;
-use std::fs::File;
-use std::io::prelude::*;
 
 type DrawnNumbers = Vec<u32>;
 
@@ -10,6 +10,53 @@ struct BingoNumber {
     marked: bool,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Line {
+    Row(usize),
+    Column(usize),
+}
+
+#[derive(Debug, PartialEq)]
+enum Event {
+    Draw(u32),
+    Win {
+        board: usize,
+        line: Line,
+        number: u32,
+        score: u32,
+        rank: usize,
+    },
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::Draw(number) => write!(f, "draw {}", number),
+            Event::Win {
+                board,
+                line,
+                number,
+                score,
+                rank,
+            } => {
+                let line = match line {
+                    Line::Row(i) => format!("row {}", i + 1),
+                    Line::Column(i) => format!("column {}", i + 1),
+                };
+                write!(
+                    f,
+                    "board {} completes {} with {}, winner #{} with score {}",
+                    board + 1,
+                    line,
+                    number,
+                    rank,
+                    score
+                )
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 struct BingoBoard {
     board: Vec<Vec<BingoNumber>>,
@@ -22,14 +69,14 @@ impl fmt::Display for BingoBoard {
         // stream: `f`. Returns `fmt::Result` which indicates whether the
         // operation succeeded or failed. Note that `write!` uses syntax which
         // is very similar to `println!`.
-        write!(f, "Board:\n");
-        self.board.iter().for_each(|r| {
-            r.iter().for_each(|bn| {
-                write!(f, "{}\t", if bn.marked { "x" } else { " " });
-            });
-            write!(f, "\n");
-        });
-        write!(f, "")
+        writeln!(f, "Board:")?;
+        for r in self.board.iter() {
+            for bn in r.iter() {
+                write!(f, "{}\t", if bn.marked { "x" } else { " " })?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
     }
 }
 
@@ -55,21 +102,25 @@ impl BingoBoard {
     }
 
     pub fn is_bingo(&self) -> bool {
+        self.winning_line().is_some()
+    }
+
+    fn winning_line(&self) -> Option<Line> {
         // Check horizontal
 
         for i in 0..self.board.len() {
             let row_complete = self.board[i].iter().all(|bn| bn.marked);
             if row_complete {
-                return row_complete;
+                return Some(Line::Row(i));
             }
         }
         for i in 0..self.board[0].len() {
-            let col_complete = self.board.iter().map(|r| r[i].marked).all(|b| b);
+            let col_complete = self.board.iter().all(|r| r[i].marked);
             if col_complete {
-                return col_complete;
+                return Some(Line::Column(i));
             }
         }
-        return false;
+        None
     }
 
     fn drawn_num(&mut self, num: u32) {
@@ -91,53 +142,71 @@ impl BingoBoard {
                 }
             }
         }
-        return score * num;
+        score * num
     }
 }
 
 pub fn day_04() -> std::io::Result<()> {
-    //let mut file = File::open("./resources/day04_small.txt")?;
-    let mut file = File::open("./resources/day04.txt")?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
+    //let contents = util::read_input("day04_small.txt")?;
+    let contents = util::read_input("day04.txt")?;
 
     //println!("contents:\n{}", contents);
     let (drawn_nums, mut boards) = parse_bingo(&contents).unwrap();
-    for num in drawn_nums {
-        for board in boards.iter_mut() {
-            board.drawn_num(num);
-            if board.is_bingo() {
-                println!("winning board: {}", board);
-                println!("Score: {}", board.score(num));
-                break;
-            }
-        }
-        if boards.iter().any(|b| b.is_bingo()) {
-            break;
-        }
+    let wins = play(&drawn_nums, &mut boards, &mut Trace::disabled());
+    if let Some(&(board, score)) = wins.first() {
+        println!("winning board: {}", boards[board]);
+        println!("Score: {}", score);
+    }
+    // Boards stop being marked once they won, so the last one shows its final state
+    if let Some(&(board, score)) = wins.last() {
+        println!("losing board: {}", boards[board]);
+        println!("Score: {}", score);
     }
 
-    // Let's try again, this time to lose
-    let (drawn_nums, mut boards) = parse_bingo(&contents).unwrap();
+    Ok(())
+}
 
-    for num in drawn_nums {
-        // Only keep the non-winning bingo
-        let mut boards = boards
-            .iter_mut()
-            .filter(|b| !b.is_bingo())
-            .collect::<Vec<&mut BingoBoard>>();
-        for board in boards.iter_mut() {
-            board.drawn_num(num);
+// Draw numbers until every board has won, recording each draw and each win.
+// Returns the board and score of every win, in winning order.
+fn play(
+    drawn_nums: &DrawnNumbers,
+    boards: &mut [BingoBoard],
+    trace: &mut Trace<Event>,
+) -> Vec<(usize, u32)> {
+    let mut wins = Vec::new();
+    for &num in drawn_nums.iter() {
+        if boards.iter().all(|b| b.is_bingo()) {
+            break;
         }
-        if boards.len() == 1 {
-            let board = &boards[0];
-            println!("losing board: {}", board);
-            println!("Score: {}", board.score(num));
-            return Ok(());
+        trace.push(Event::Draw(num));
+        for (i, board) in boards.iter_mut().enumerate() {
+            if board.is_bingo() {
+                continue;
+            }
+            board.drawn_num(num);
+            if let Some(line) = board.winning_line() {
+                let score = board.score(num);
+                wins.push((i, score));
+                trace.push(Event::Win {
+                    board: i,
+                    line,
+                    number: num,
+                    score,
+                    rank: wins.len(),
+                });
+            }
         }
     }
+    wins
+}
 
-    return Ok(());
+pub fn explain() -> std::io::Result<()> {
+    let contents = util::read_input("day04.txt")?;
+    let (drawn_nums, mut boards) = parse_bingo(&contents).unwrap();
+    let mut trace = Trace::new();
+    play(&drawn_nums, &mut boards, &mut trace);
+    print!("{}", trace);
+    Ok(())
 }
 
 fn parse_bingo(input: &str) -> Result<(DrawnNumbers, Vec<BingoBoard>), String> {
@@ -159,3 +228,39 @@ fn parse_bingo(input: &str) -> Result<(DrawnNumbers, Vec<BingoBoard>), String> {
 
     Ok((drawn_num, boards))
 }
+
+#[test]
+fn play_small_example() {
+    let contents = include_str!("../resources/day04_small.txt");
+    let (drawn_nums, mut boards) = parse_bingo(contents).unwrap();
+    let mut trace = Trace::new();
+    let results = play(&drawn_nums, &mut boards, &mut trace);
+    assert_eq!(results.first(), Some(&(2, 4512)));
+    assert_eq!(results.last(), Some(&(1, 1924)));
+    let wins: Vec<_> = trace
+        .events()
+        .iter()
+        .filter(|e| matches!(e, Event::Win { .. }))
+        .collect();
+    assert_eq!(
+        wins.first(),
+        Some(&&Event::Win {
+            board: 2,
+            line: Line::Row(0),
+            number: 24,
+            score: 4512,
+            rank: 1,
+        })
+    );
+    assert!(matches!(
+        wins.last(),
+        Some(Event::Win {
+            board: 1,
+            score: 1924,
+            rank: 3,
+            ..
+        })
+    ));
+    // Drawing stops once the last board has won
+    assert_eq!(trace.events().last(), wins.last().copied());
+}
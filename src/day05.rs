@@ -1,116 +1,46 @@
-use std::cmp;
-use std::fs::File;
-use std::io::prelude::*;
-use std::str::FromStr;
-
-use std::collections::HashMap;
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-struct Point<T> {
-    x: T,
-    y: T,
-}
-
-impl<T> Point<T>
-where
-    T: FromStr,
-{
-    fn from((x, y): (T, T)) -> Self {
-        Point { x, y }
-    }
-}
-
-/*Here lies my attempt at using try_from.
-*Unwrap couldn't be called because of it not implementing
-the correct error type or something similar*/
-//impl<T> TryFrom<&str> for Point<T>
-//where
-//    T: FromStr,
-//{
-//    type Error = &'static str;
-//    fn try_from(input: &str) -> Result<Self, Self::Error> {
-//        let result = input.trim().split_once(',');
-//        if let Some((x, y)) = result {
-//            let x = x.parse::<T>().unwrap();
-//            let y = y.parse::<T>();
-//            Ok(Point::from((x, y)))
-//        } else {
-//            Err("failed to parse")
-//        }
-//    }
-//}
+use crate::grid::SparseGrid;
+use crate::point::Point;
+use crate::util;
+use std::io::BufRead;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 struct Segment {
-    start: Point<i32>,
-    end: Point<i32>,
+    start: Point<i64>,
+    end: Point<i64>,
 }
 
 impl Segment {
     fn from_str(input: &str) -> Result<Self, String> {
         let (p1, p2) = input
             .split_once(" -> ")
-            .expect("Failed to parse into points");
-        let p1 = p1.trim().split_once(',').expect("can't split p1");
-        let p2 = p2.trim().split_once(',').expect("can't split p2");
-
-        let p1 = (p1.0.parse::<i32>().unwrap(), p1.1.parse::<i32>().unwrap());
-        let p2 = (p2.0.parse::<i32>().unwrap(), p2.1.parse::<i32>().unwrap());
+            .ok_or_else(|| format!("expected x1,y1 -> x2,y2: {}", input))?;
         Ok(Self {
-            start: Point::from(p1),
-            end: Point::from(p2),
+            start: p1.parse().map_err(|e| format!("{}: {}", p1, e))?,
+            end: p2.parse().map_err(|e| format!("{}: {}", p2, e))?,
         })
     }
 
-    fn generate_points(&self) -> Vec<Point<i32>> {
-        // Return either a vector or an iterator?
-        // Should learn iterator
-        if self.start.x == self.end.x {
-            let start = cmp::min(self.start.y, self.end.y);
-            let end = cmp::max(self.start.y, self.end.y);
-            return (start..=end)
-                .map(|y| Point { x: self.start.x, y })
-                .collect::<Vec<_>>();
-        } else if self.start.y == self.end.y {
-            let start = cmp::min(self.start.x, self.end.x);
-            let end = cmp::max(self.start.x, self.end.x);
-            return (start..=end)
-                .map(|x| Point { y: self.start.y, x })
-                .collect::<Vec<_>>();
-        }
-        // Calculate diagonal lines
-        // Assume perfect diagonal
-        let (start, end) = if self.start.x < self.end.x {
-            (self.start, self.end)
-        } else {
-            (self.end, self.start)
-        };
-
-        let next = if start.y < end.y {
-            // Increase the y if the start y is lower
-            |y: i32, i: usize| y + i as i32
-        } else {
-            |y: i32, i: usize| y - i as i32
-        };
-
-        return (start.x..=end.x)
-            .enumerate()
-            .map(|(c, x)| Point {
-                x,
-                y: next(start.y, c),
-            })
-            .collect::<_>();
+    fn generate_points(&self) -> Vec<Point<i64>> {
+        // Segments are horizontal, vertical or perfect diagonals, so one
+        // unit step in each direction walks through all the integer points
+        let step = (self.end - self.start).signum();
+        (0..=self.start.chebyshev(self.end))
+            .map(|i| self.start + step * i)
+            .collect()
     }
 }
 
 struct Diagram {
-    map: HashMap<Point<i32>, i32>,
+    map: SparseGrid<i32>,
+    // Number of points covered by at least 2 segments, kept up to date by `add`
+    overlaps: usize,
 }
 
 impl Diagram {
     fn new() -> Self {
         Diagram {
-            map: HashMap::new(),
+            map: SparseGrid::new(),
+            overlaps: 0,
         }
     }
 
@@ -118,40 +48,66 @@ impl Diagram {
         // Add all the integer point between 2 segments
         let points = segment.generate_points();
         points.iter().for_each(|&p| {
-            *self.map.entry(p.clone()).or_insert(0) += 1;
+            let count = self.map.entry_or_default(p);
+            *count += 1;
+            if *count == 2 {
+                self.overlaps += 1;
+            }
         })
     }
 }
 
 pub fn day_05() -> Result<(), String> {
-    //let contents = read_file("./resources/day05_small.txt").expect("Could not open file");
-    let contents = read_file("./resources/day05.txt").expect("Could not open file");
+    //let contents = util::read_input("day05_small.txt").expect("Could not open file");
+    let contents = util::read_input("day05.txt").expect("Could not open file");
     let segments = parse_into_segments(&contents)?;
 
     let mut diagram = Diagram::new();
 
     segments.iter().for_each(|d| diagram.add(d));
 
-    let overlap_count = diagram.map.iter().filter(|(_, c)| **c > 1).count();
-    println!("Overlap count: {}", overlap_count);
+    println!("Overlap count: {}", diagram.overlaps);
 
-    return Ok(());
+    Ok(())
 }
 
-fn read_file(file: &str) -> std::io::Result<String> {
-    let mut file = File::open(file)?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
+// Same answer as `day_05`, adding each segment to the diagram as soon as it is
+// read. Memory only depends on the area covered, not on the number of
+// segments.
+pub fn day_05_streaming(reader: impl BufRead) -> util::Result<()> {
+    let diagram = draw(reader)?;
+    println!("Overlap count: {}", diagram.overlaps);
+    Ok(())
+}
 
-    Ok(contents)
+fn draw(reader: impl BufRead) -> util::Result<Diagram> {
+    let mut diagram = Diagram::new();
+    util::for_each_line(reader, |_, line| {
+        diagram.add(&Segment::from_str(line)?);
+        Ok(())
+    })?;
+    Ok(diagram)
 }
 
 fn parse_into_segments(input: &str) -> Result<Vec<Segment>, String> {
-    let segments = input
-        .trim()
-        .lines()
-        .map(|l| Segment::from_str(l))
-        .map(|s| s.unwrap())
-        .collect::<Vec<_>>();
-    Ok(segments)
+    input.trim().lines().map(Segment::from_str).collect()
+}
+
+#[test]
+fn draw_small_example() {
+    let contents = include_str!("../resources/day05_small.txt");
+    let diagram = draw(contents.as_bytes()).unwrap();
+    assert_eq!(diagram.overlaps, 12);
+    assert_eq!(
+        diagram.overlaps,
+        diagram.map.iter().filter(|(_, c)| **c > 1).count()
+    );
+}
+
+#[test]
+fn draw_reports_malformed_line() {
+    let contents = "0,9 -> 5,9\n8,0 8,8\n";
+    let error = draw(contents.as_bytes()).err().unwrap().to_string();
+    assert_eq!(error, "line 2: expected x1,y1 -> x2,y2: 8,0 8,8");
+    assert!(parse_into_segments(contents).is_err());
 }
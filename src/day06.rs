@@ -1,6 +1,10 @@
+use crate::animate::{Animation, Frame};
+use crate::checkpoint::{self, Checkpoint, Session};
+use crate::simulation::{self, Simulation};
+use crate::util;
 use std::fmt;
-use std::fs::File;
-use std::io::prelude::*;
+
+const HISTOGRAM_WIDTH: u64 = 60;
 
 struct Fish {
     timer: u32,
@@ -12,7 +16,7 @@ struct FishState {
     fish_count: u64, // There could be a _lot_ of fishes
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 struct FishPopState {
     day: u32,
     fishes: Vec<FishState>,
@@ -36,8 +40,16 @@ impl FishPopState {
         self.fishes[fish.timer as usize].fish_count += 1;
     }
 
-    // Advance the simulation for a day
-    fn step(&mut self) {
+    // Advance the simulation for a day. Fails without changing anything once
+    // the population no longer fits in a u64, which takes a bit over 400
+    // days.
+    fn step(&mut self) -> util::Result<()> {
+        // Every fish at timer 0 adds one, and no bucket is bigger than the
+        // total, so checking the total is enough
+        let born_fish_count = self.fishes[0].fish_count;
+        self.count()
+            .checked_add(born_fish_count)
+            .ok_or_else(|| format!("fish population overflows on day {}", self.day + 1))?;
         self.day += 1;
         // Decrement all the TimerState and process timerState at 0
 
@@ -46,12 +58,11 @@ impl FishPopState {
         // Move a whole generation down 1 tick
         self.fishes.iter().enumerate().for_each(|(c, f)| {
             if c != 0 {
-                next_generation[c - 1] = f.clone();
+                next_generation[c - 1] = *f;
             }
         });
 
         // Add new fish from timer0 to timer8
-        let born_fish_count = self.fishes[0].fish_count;
         next_generation[8] = FishState {
             fish_count: born_fish_count,
         };
@@ -63,12 +74,78 @@ impl FishPopState {
 
         // Replace the old generation with the new
         self.fishes = next_generation;
+        Ok(())
     }
 
     fn count(&self) -> u64 {
         // Loop over all the fishes and get the number of fish in that each state
         self.fishes.iter().map(|f| f.fish_count).sum()
     }
+
+    // One bar per timer value, scaled so the biggest bucket fills the width
+    fn histogram(&self) -> String {
+        let max = self.fishes.iter().map(|f| f.fish_count).max().unwrap_or(0);
+        self.fishes
+            .iter()
+            .enumerate()
+            .map(|(timer, f)| {
+                let width = (f.fish_count * HISTOGRAM_WIDTH)
+                    .checked_div(max)
+                    .unwrap_or(0);
+                format!(
+                    "{} |{:<w$}| {}\n",
+                    timer,
+                    "#".repeat(width as usize),
+                    f.fish_count,
+                    w = HISTOGRAM_WIDTH as usize
+                )
+            })
+            .collect()
+    }
+}
+
+impl Checkpoint for FishPopState {
+    const KIND: &'static str = "fish";
+
+    fn to_lines(&self) -> Vec<String> {
+        let timers: Vec<String> = self
+            .fishes
+            .iter()
+            .map(|f| f.fish_count.to_string())
+            .collect();
+        vec![
+            format!("day {}", self.day),
+            format!("timers {}", timers.join(" ")),
+        ]
+    }
+
+    fn from_lines(lines: &[&str]) -> util::Result<Self> {
+        let day = checkpoint::field(lines.first(), "day")?;
+        let timers = checkpoint::field(lines.get(1), "timers")?;
+        if day.len() != 1 || timers.len() != 9 {
+            return Err("expected a day and 9 timer counts".into());
+        }
+        Ok(FishPopState {
+            day: day[0].parse()?,
+            fishes: timers
+                .iter()
+                .map(|c| c.parse().map(|fish_count| FishState { fish_count }))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl Simulation for FishPopState {
+    // Fish never die, so a population that isn't empty never repeats
+    type Fingerprint = Vec<FishState>;
+
+    fn step(&mut self) -> util::Result<()> {
+        FishPopState::step(self)
+    }
+
+    fn fingerprint(&self) -> Self::Fingerprint {
+        self.fishes.clone()
+    }
 }
 
 impl fmt::Display for FishPopState {
@@ -82,10 +159,21 @@ impl fmt::Display for FishPopState {
     }
 }
 pub fn day_06() -> Result<(), String> {
-    //let contents = read_file("./resources/day06_small.txt").expect("Could not open file");
-    let contents = read_file("./resources/day06.txt").expect("Could not open file");
+    //let contents = util::read_input("day06_small.txt").expect("Could not open file");
+    let contents = util::read_input("day06.txt").expect("Could not open file");
 
     println!("contents: {}", contents);
+    let mut fish_pop = parse(&contents);
+
+    println!("Fish pop day: {}", fish_pop);
+    let max_days = 256;
+    simulation::run(&mut fish_pop, max_days).map_err(|e| e.to_string())?;
+    println!("Fish pop day: {}", fish_pop);
+
+    Ok(())
+}
+
+fn parse(contents: &str) -> FishPopState {
     let mut fish_pop = FishPopState::new();
     contents
         .trim()
@@ -93,21 +181,74 @@ pub fn day_06() -> Result<(), String> {
         .map(|s| s.parse::<u32>().unwrap())
         .map(|d| Fish { timer: d })
         .for_each(|f| fish_pop.add_fish(f));
+    fish_pop
+}
 
+// Timer histogram of the population, one frame per day.
+pub(crate) fn frames(contents: &str, max_days: u32) -> impl Iterator<Item = Frame> {
+    let mut fish_pop = parse(contents);
+    let mut started = false;
+    std::iter::from_fn(move || {
+        if started {
+            if fish_pop.day >= max_days {
+                return None;
+            }
+            fish_pop.step().ok()?;
+        }
+        started = true;
+        Some(Frame {
+            caption: format!("{}", fish_pop),
+            body: fish_pop.histogram(),
+            highlight: '#',
+        })
+    })
+}
+
+pub fn simulate(session: &Session) -> util::Result<()> {
+    let fish_pop = session.run_simulation(|| Ok(parse(&util::read_input("day06.txt")?)))?;
     println!("Fish pop day: {}", fish_pop);
-    let max_days = 256;
-    for i in 1..=max_days {
-        fish_pop.step();
-        println!("Fish pop day: {}", fish_pop);
-    }
+    Ok(())
+}
 
-    return Ok(());
+pub fn animate(animation: &Animation) -> std::io::Result<()> {
+    let contents = util::read_input("day06.txt")?;
+    animation.play(frames(&contents, 256))
 }
 
-fn read_file(file: &str) -> std::io::Result<String> {
-    let mut file = File::open(file)?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
+#[test]
+fn checkpoint_round_trip() {
+    let mut fish_pop = parse("3,4,3,1,2");
+    for _ in 0..18 {
+        fish_pop.step().unwrap();
+    }
+    let saved = checkpoint::to_string(&fish_pop);
+    let mut resumed: FishPopState = checkpoint::from_str(&saved).unwrap();
+    assert_eq!(resumed, fish_pop);
+    for _ in 18..80 {
+        resumed.step().unwrap();
+    }
+    assert_eq!(resumed.count(), 5934);
+}
 
-    Ok(contents)
+#[test]
+fn step_fails_once_the_population_overflows() {
+    let session = Session {
+        steps: 1000,
+        ..Default::default()
+    };
+    let err = session
+        .run_simulation(|| Ok(parse("3,4,3,1,2")))
+        .err()
+        .unwrap();
+    assert!(err
+        .to_string()
+        .starts_with("fish population overflows on day"));
+    let mut fish_pop = parse("3,4,3,1,2");
+    assert!(simulation::run(&mut fish_pop, 1000).is_err());
+
+    let mut fish_pop = parse("3,4,3,1,2");
+    while fish_pop.step().is_ok() {}
+    let stuck = fish_pop.clone();
+    assert!(fish_pop.step().is_err());
+    assert_eq!(fish_pop, stuck);
 }
@@ -1,11 +1,44 @@
-use std::fs::File;
-use std::io::prelude::*;
+use crate::explain::Trace;
+use crate::util;
+use std::fmt;
 
 type CrabVec = Vec<u32>;
 
+#[derive(Debug, PartialEq)]
+enum Event {
+    Range {
+        min: u32,
+        max: u32,
+    },
+    Best {
+        model: &'static str,
+        position: u32,
+        cost: u32,
+    },
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::Range { min, max } => {
+                write!(f, "try every position between {} and {}", min, max)
+            }
+            Event::Best {
+                model,
+                position,
+                cost,
+            } => write!(
+                f,
+                "{} fuel: align on position {} for a cost of {}",
+                model, position, cost
+            ),
+        }
+    }
+}
+
 pub fn day_07() -> Result<(), String> {
-    //let contents = read_file("./resources/day07_small.txt").expect("Could not open file");
-    let contents = read_file("./resources/day07.txt").expect("Could not open file");
+    //let contents = util::read_input("day07_small.txt").expect("Could not open file");
+    let contents = util::read_input("day07.txt").expect("Could not open file");
 
     println!("contents: {}", contents);
     let crab_positions = parse_contents(&contents);
@@ -14,8 +47,6 @@ pub fn day_07() -> Result<(), String> {
     println!("mean of crab pos: {:?}", mean(&crab_positions));
     println!("mean of crab pos: {:?}", median(&crab_positions));
 
-    let min = crab_positions.iter().min().unwrap().clone();
-    let max = crab_positions.iter().max().unwrap().clone();
     /*
     for pos in min..=max {
         println!(
@@ -26,26 +57,55 @@ pub fn day_07() -> Result<(), String> {
     }
     */
 
-    let min_fuel_needed = (min..=max)
-        .map(|pos| calculate_fuel_need(&crab_positions, pos))
-        .min()
-        .unwrap();
+    let mut trace = Trace::disabled();
+    let (_, min_fuel_needed) = align(&crab_positions, "constant", calculate_fuel_need, &mut trace);
     println!("Min fuel required: {}", min_fuel_needed);
-    let min_fuel_needed_v2 = (min..=max)
-        .map(|pos| calculate_fuel_need_v2(&crab_positions, pos))
-        .min()
-        .unwrap();
+    let (_, min_fuel_needed_v2) = align(
+        &crab_positions,
+        "increasing",
+        calculate_fuel_need_v2,
+        &mut trace,
+    );
     println!("Min fuel required v2: {}", min_fuel_needed_v2);
 
-    return Ok(());
+    Ok(())
 }
 
-fn read_file(file: &str) -> std::io::Result<String> {
-    let mut file = File::open(file)?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
+pub fn explain() -> std::io::Result<()> {
+    let contents = util::read_input("day07.txt")?;
+    let crab_positions = parse_contents(&contents);
+    let mut trace = Trace::new();
+    align(&crab_positions, "constant", calculate_fuel_need, &mut trace);
+    align(
+        &crab_positions,
+        "increasing",
+        calculate_fuel_need_v2,
+        &mut trace,
+    );
+    print!("{}", trace);
+    Ok(())
+}
 
-    Ok(contents)
+// Cheapest position to align the crabs on, with its cost
+fn align(
+    crabs: &CrabVec,
+    model: &'static str,
+    fuel_need: fn(&CrabVec, u32) -> u32,
+    trace: &mut Trace<Event>,
+) -> (u32, u32) {
+    let min = *crabs.iter().min().unwrap();
+    let max = *crabs.iter().max().unwrap();
+    trace.push(Event::Range { min, max });
+    let (position, cost) = (min..=max)
+        .map(|pos| (pos, fuel_need(crabs, pos)))
+        .min_by_key(|&(_, cost)| cost)
+        .unwrap();
+    trace.push(Event::Best {
+        model,
+        position,
+        cost,
+    });
+    (position, cost)
 }
 
 fn parse_contents(input: &str) -> CrabVec {
@@ -60,19 +120,19 @@ fn calculate_fuel_need(crabs: &CrabVec, destination: u32) -> u32 {
     let abs_diffs = crabs
         .iter()
         .map(|&p| (p as i64) - (destination as i64))
-        .map(|diff| diff.abs() as u32)
+        .map(|diff| diff.unsigned_abs() as u32)
         .sum::<u32>();
-    return abs_diffs;
+    abs_diffs
 }
 
 fn calculate_fuel_need_v2(crabs: &CrabVec, destination: u32) -> u32 {
     let abs_diffs = crabs
         .iter()
         .map(|&p| (p as i64) - (destination as i64))
-        .map(|diff| diff.abs() as u32)
+        .map(|diff| diff.unsigned_abs() as u32)
         .map(triangular_number)
         .sum::<u32>();
-    return abs_diffs;
+    abs_diffs
 }
 
 fn triangular_number(n: u32) -> u32 {
@@ -83,16 +143,30 @@ fn mean(numbers: &[u32]) -> f64 {
     let sum = numbers.iter().sum::<u32>() as f64;
     let count = numbers.len();
 
-    let mean = sum / count as f64;
-    mean
+    sum / count as f64
 }
 
 fn median(numbers: &[u32]) -> f64 {
     let len = numbers.len();
     let mid = len / 2;
-    if len % 2 == 0 {
+    if len.is_multiple_of(2) {
         mean(&numbers[(mid - 1)..(mid + 1)])
     } else {
         f64::from(numbers[mid])
     }
 }
+
+#[test]
+fn align_small_example() {
+    let crabs = parse_contents(include_str!("../resources/day07_small.txt"));
+    let mut trace = Trace::new();
+    assert_eq!(
+        align(&crabs, "constant", calculate_fuel_need, &mut trace),
+        (2, 37)
+    );
+    assert_eq!(
+        align(&crabs, "increasing", calculate_fuel_need_v2, &mut trace),
+        (5, 168)
+    );
+    assert_eq!(trace.events()[0], Event::Range { min: 0, max: 16 });
+}
@@ -1,34 +1,8 @@
-use std::collections::{HashMap, HashSet};
-use std::fs::File;
-use std::io::prelude::*;
-
-type SegmentMap = HashMap<char, HashSet<char>>;
-type DigitToSegment = HashMap<u32, HashSet<char>>;
-
-struct Mapping {
-    map: SegmentMap,
-    digit_to_segment: DigitToSegment,
-}
-
-impl Mapping {
-    fn new(digit_to_segment: &DigitToSegment) -> Self {
-        let segments = "abcdefg";
-        Self {
-            map: segments
-                .chars()
-                .map(|c| (c, segments.chars().collect()))
-                .collect(),
-            digit_to_segment: digit_to_segment.clone(),
-        }
-    }
-    fn reduce(&mut self, digit: u32, set: HashSet<char>) {
-        todo!()
-    }
-}
+use crate::util;
 
 pub fn day_08() -> Result<(), String> {
-    let contents = read_file("./resources/day08_small.txt").expect("Could not open file");
-    //let contents = read_file("./resources/day08.txt").expect("Could not open file");
+    let contents = util::read_input("day08_small.txt").expect("Could not open file");
+    //let contents = util::read_input("day08.txt").expect("Could not open file");
 
     println!("contents: {}", contents);
 
@@ -40,31 +14,7 @@ pub fn day_08() -> Result<(), String> {
         .sum();
     println!("digits_1478 count: {:?}", digits_1478);
 
-    let digit_to_segment = HashMap::from([
-        (0, "abcefg"),
-        (1, "cf"),
-        (2, "acdeg"),
-        (3, "acdfg"),
-        (4, "bcdf"),
-        (5, "abdfg"),
-        (6, "abdefg"),
-        (7, "acf"),
-        (8, "abcdefg"),
-        (9, "abcdfg"),
-    ])
-    .iter()
-    .map(|(&k, v)| (k as u32, v.chars().collect()))
-    .collect::<DigitToSegment>();
-
-    return Ok(());
-}
-
-fn read_file(file: &str) -> std::io::Result<String> {
-    let mut file = File::open(file)?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
-
-    Ok(contents)
+    Ok(())
 }
 
 fn parse_patterns_and_output(input: &str) -> Vec<(Vec<&str>, Vec<&str>)> {
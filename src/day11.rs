@@ -1,3 +1,8 @@
+use crate::animate::{Animation, Frame};
+use crate::checkpoint::{self, Checkpoint, Session};
+use crate::grid::{Grid, Topology};
+use crate::point::Point;
+use crate::simulation::{self, Simulation};
 use crate::util;
 use std::fmt;
 
@@ -22,110 +27,50 @@ impl Octopus {
             self.level = 0;
         }
     }
-
-    fn flash(&mut self, map: &mut Map<Octopus>, pos: Vec2) {
-        self.flashing = true;
-        map.set(pos, self.clone());
-        map.neighbor_positions(pos).for_each(|n_pos| {
-            if let Some(mut n_o) = map.get(n_pos) {
-                n_o.increase_level();
-                map.set(n_pos, n_o);
-                if !n_o.flashing && n_o.over_threshold() {
-                    n_o.flash(map, n_pos);
-                }
-            }
-        });
-    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-struct Vec2 {
-    x: i64,
-    y: i64,
-}
+// Steps to simulate before giving up on a cycle
+const CYCLE_LIMIT: usize = 100_000;
 
-struct Map<T> {
-    size: Vec2,
-    octopi: Vec<T>,
+#[derive(Clone)]
+struct Map {
+    octopi: Grid<Octopus>,
+    steps: usize,
 }
 
-impl<T> Map<T>
-where
-    T: Default + Copy,
-{
-    fn new(size: Vec2) -> Self {
-        let num_tiles = size.x * size.y;
-        Self {
-            size,
-            octopi: (0..num_tiles)
-                .into_iter()
-                .map(|_| Default::default())
-                .collect(),
-        }
+impl Map {
+    fn new(octopi: Grid<Octopus>) -> Self {
+        Self { octopi, steps: 0 }
     }
 
-    fn index(&self, pos: Vec2) -> Option<usize> {
-        if (0..self.size.x).contains(&pos.x) && (0..self.size.y).contains(&pos.y) {
-            Some((pos.x + pos.y * self.size.x) as _)
-        } else {
-            None
-        }
-    }
-
-    fn set(&mut self, pos: Vec2, octopus: T) {
-        if let Some(index) = self.index(pos) {
-            self.octopi[index] = octopus;
+    fn flash(&mut self, pos: Point<usize>) {
+        self.octopi[pos].flashing = true;
+        let neighbors: Vec<_> = self.octopi.neighbors8(pos).collect();
+        for n_pos in neighbors {
+            let n_o = &mut self.octopi[n_pos];
+            n_o.increase_level();
+            if !n_o.flashing && n_o.over_threshold() {
+                self.flash(n_pos);
+            }
         }
     }
 
-    fn get(&self, pos: Vec2) -> Option<T> {
-        self.index(pos).map(|index| self.octopi[index])
-    }
-
-    fn neighbor_positions(&self, pos: Vec2) -> impl Iterator<Item = Vec2> {
-        (-1..=1)
-            .flat_map(|dx| (-1..=1).map(move |dy| (dx, dy)))
-            .filter(|&(dx, dy)| !(dx == 0 && dy == 0))
-            .map(move |(dx, dy)| Vec2 {
-                x: pos.x + dx,
-                y: pos.y + dy,
-            })
-    }
-}
-
-impl Map<Octopus> {
     fn step(&mut self) -> usize {
         // Increase level of all octopi by 1
-        let (row, col) = (self.size.y, self.size.x);
-        for c in 0..col {
-            for r in 0..row {
-                let pos = Vec2 { x: r, y: c };
-                let mut o = self.get(pos).unwrap();
-                o.increase_level();
-                self.set(pos, o);
-            }
-        }
-        for c in 0..col {
-            for r in 0..row {
-                let pos = Vec2 { x: r, y: c };
-                let mut o = self.get(pos).unwrap();
-                if o.over_threshold() && !o.flashing {
-                    o.flash(self, pos);
-                }
+        self.octopi.iter_mut().for_each(Octopus::increase_level);
+        let positions: Vec<_> = self.octopi.positions().collect();
+        for pos in positions {
+            let o = self.octopi[pos];
+            if o.over_threshold() && !o.flashing {
+                self.flash(pos);
             }
         }
 
         let flash_count = self.octopi.iter().filter(|o| o.flashing).count();
+        self.steps += 1;
 
         // Once no new neighbor flashes, loop over all octopis and set flashing ones to 0
-        for c in 0..col {
-            for r in 0..row {
-                let pos = Vec2 { x: r, y: c };
-                let mut o = self.get(pos).unwrap();
-                o.reset();
-                self.set(pos, o);
-            }
-        }
+        self.octopi.iter_mut().for_each(Octopus::reset);
 
         flash_count
     }
@@ -135,71 +80,153 @@ impl Map<Octopus> {
     }
 }
 
-impl fmt::Display for Map<Octopus> {
-    // This trait requires `fmt` with this exact signature.
+impl Simulation for Map {
+    type Fingerprint = Vec<u32>;
+
+    fn step(&mut self) -> util::Result<()> {
+        Map::step(self);
+        Ok(())
+    }
+
+    fn fingerprint(&self) -> Self::Fingerprint {
+        self.octopi.iter().map(|o| o.level).collect()
+    }
+}
+
+impl fmt::Display for Map {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        // Write strictly the first element into the supplied output
-        // stream: `f`. Returns `fmt::Result` which indicates whether the
-        // operation succeeded or failed. Note that `write!` uses syntax which
-        // is very similar to `println!`.
-        let (row, col) = (self.size.y, self.size.x);
-        for c in 0..col {
-            for r in 0..row {
-                let o = self.get(Vec2 { x: r, y: c }).unwrap();
-                write!(f, "{}", o.level)?;
-            }
-            write!(f, "\n")?;
+        // Levels are single digits between steps
+        let rendered = self
+            .octopi
+            .render(|o| char::from_digit(o.level, 10).unwrap_or('*'));
+        write!(f, "{}", rendered)
+    }
+}
+
+impl Checkpoint for Map {
+    const KIND: &'static str = "octopi";
+
+    // Levels are written one row per line. They are all single digits
+    // between steps, since flashing octopi are reset to 0. A wrapping world
+    // is marked after the rows.
+    fn to_lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("steps {}", self.steps),
+            format!("size {} {}", self.octopi.width(), self.octopi.height()),
+        ];
+        lines.extend(
+            format!("{}", self)
+                .lines()
+                .map(|row| format!("row {}", row)),
+        );
+        if let Topology::Torus = self.octopi.topology() {
+            lines.push("topology torus".to_string());
         }
+        lines
+    }
 
-        write!(f, "")
+    fn from_lines(lines: &[&str]) -> util::Result<Self> {
+        let steps = checkpoint::field(lines.first(), "steps")?;
+        let size = checkpoint::field(lines.get(1), "size")?;
+        if steps.len() != 1 || size.len() != 2 {
+            return Err("expected a step count and a size".into());
+        }
+        let (width, height): (usize, usize) = (size[0].parse()?, size[1].parse()?);
+        let mut rows = String::new();
+        for y in 0..height {
+            let row = checkpoint::field(lines.get(y + 2), "row")?;
+            let row = row.first().copied().unwrap_or_default();
+            if row.len() != width {
+                return Err(format!("row {} should have {} levels", y, width).into());
+            }
+            rows.push_str(row);
+            rows.push('\n');
+        }
+        let topology = match lines.get(height + 2) {
+            None => Topology::Bounded,
+            Some(&"topology torus") => Topology::Torus,
+            Some(line) => return Err(format!("unexpected line '{}'", line).into()),
+        };
+        let mut map = Map::new(parse_octopi(&rows)?.with_topology(topology));
+        map.steps = steps[0].parse()?;
+        Ok(map)
     }
 }
 
-fn parse(input: &str) -> Map<Octopus> {
-    let octopi: Vec<_> = input
-        .trim()
-        .lines()
-        .enumerate()
-        .map(|(y, line)| {
-            line.chars().enumerate().map(move |(x, c)| {
-                (
-                    Octopus {
-                        level: c.to_digit(10).unwrap(),
-                        flashing: false,
-                    },
-                    Vec2 {
-                        x: x as i64,
-                        y: y as i64,
-                    },
-                )
-            })
+fn parse_octopi(input: &str) -> util::Result<Grid<Octopus>> {
+    Grid::parse_with(input, |c| {
+        Ok(Octopus {
+            level: c.to_digit(10).ok_or(format!("invalid level {}", c))?,
+            flashing: false,
         })
-        .flatten()
-        .collect();
+    })
+}
 
-    let col = input
-        .trim()
-        .lines()
-        .into_iter()
-        .collect::<Vec<&str>>()
-        .len();
-    let row = input.trim().split_once('\n').unwrap().0.len();
-    let size = Vec2 {
-        x: row as i64,
-        y: col as i64,
-    };
+fn parse(input: &str) -> Map {
+    Map::new(
+        parse_octopi(input)
+            .unwrap()
+            .with_topology(Topology::Bounded),
+    )
+}
 
-    let mut map: Map<Octopus> = Map::new(size);
+// One frame per step until the whole cavern flashes at once. Octopi that just
+// flashed are back at level 0, so that is the digit to highlight.
+pub(crate) fn frames(contents: &str) -> impl Iterator<Item = Frame> {
+    let mut map = parse(contents);
+    let mut step = 0;
+    let mut flashes = 0;
+    let mut done = false;
+    std::iter::from_fn(move || {
+        if done {
+            return None;
+        }
+        if step > 0 {
+            flashes = map.step();
+        }
+        done = map.all_flashed();
+        let frame = Frame {
+            caption: format!("step {}, flashes: {}", step, flashes),
+            body: format!("{}", map),
+            highlight: '0',
+        };
+        step += 1;
+        Some(frame)
+    })
+}
+
+// Run on a torus when `wrap` is set. A resumed checkpoint keeps the world it
+// was saved with. Without checkpoints, repeating states are skipped so any
+// number of steps can be run.
+pub fn simulate(session: &Session, wrap: bool) -> util::Result<()> {
+    let topology = if wrap {
+        Topology::Torus
+    } else {
+        Topology::Bounded
+    };
+    let initial = || -> util::Result<Map> {
+        let octopi = parse_octopi(&util::read_input("day11.txt")?)?;
+        Ok(Map::new(octopi.with_topology(topology)))
+    };
+    let map = if session.resume.is_none() && session.save_to.is_none() {
+        let mut map = simulation::fast_forward(&initial()?, session.steps, CYCLE_LIMIT)?;
+        map.steps = session.steps;
+        map
+    } else {
+        session.run_simulation(initial)?
+    };
+    println!("octopi after {} steps: \n{}", map.steps, map);
+    Ok(())
+}
 
-    octopi.iter().for_each(|(o, pos)| {
-        map.set(*pos, *o);
-    });
-    map
+pub fn animate(animation: &Animation) -> std::io::Result<()> {
+    let contents = util::read_input("day11.txt")?;
+    animation.play(frames(&contents))
 }
 
 pub fn day_11() -> Result<(), String> {
-    //let contents = util::read_file("./resources/day11_small.txt").expect("Could not open file");
-    let contents = util::read_file("./resources/day11.txt").expect("Could not open file");
+    //let contents = util::read_input("day11_small.txt").expect("Could not open file");
+    let contents = util::read_input("day11.txt").expect("Could not open file");
 
     println!("contents: \n{}", contents);
 
@@ -211,27 +238,31 @@ pub fn day_11() -> Result<(), String> {
     for _ in 0..100 {
         total_flashes += map.step();
     }
-    let mut step = 100;
-    while !map.all_flashed() {
-        map.step();
-        step += 1;
-    }
-
     println!("octopi after 100 steps: \n{}", map);
+    let cycle = simulation::find_cycle(&map, CYCLE_LIMIT).map_err(|e| e.to_string())?;
+    let step = 100
+        + simulation::run_until(&mut map, CYCLE_LIMIT, Map::all_flashed)
+            .map_err(|e| e.to_string())?
+            .ok_or("octopi never flash all at once")?;
+
+    match cycle {
+        Some(cycle) => println!("octopi: {}", cycle.offset(100)),
+        None => println!("octopi don't repeat within {} steps", CYCLE_LIMIT),
+    }
     println!("Total flash: {}", total_flashes);
     println!("all octopi flash at step : {}", step);
 
-    return Ok(());
+    Ok(())
 }
 
 #[test]
 fn test_neighbor_positions() {
     use std::collections::HashSet;
 
-    let map = Map::<()>::new(Vec2 { x: 3, y: 3 });
-    let positions: HashSet<_> = map
-        .neighbor_positions(Vec2 { x: 1, y: 1 })
-        .map(|v| (v.x, v.y))
+    let grid = Grid::filled(3, 3, ());
+    let positions: HashSet<_> = grid
+        .neighbors8(Point::new(1, 1))
+        .map(|p| (p.x, p.y))
         .collect();
     for p in &[
         (0, 0),
@@ -247,6 +278,37 @@ fn test_neighbor_positions() {
     }
 }
 
+#[test]
+fn test_frames_stop_when_all_flashed() {
+    let contents = include_str!("../resources/day11_small.txt");
+    let frames: Vec<_> = frames(contents).collect();
+    // Initial state plus the 195 steps of the example
+    assert_eq!(frames.len(), 196);
+    assert!(frames
+        .last()
+        .unwrap()
+        .body
+        .lines()
+        .all(|l| l.trim_matches('0').is_empty()));
+}
+
+#[test]
+fn test_checkpoint_round_trip() {
+    let contents = include_str!("../resources/day11_small.txt");
+    let mut map = parse(contents);
+    for _ in 0..10 {
+        map.step();
+    }
+    let saved = checkpoint::to_string(&map);
+    let mut resumed: Map = checkpoint::from_str(&saved).unwrap();
+    assert_eq!(resumed.steps, 10);
+    assert_eq!(format!("{}", resumed), format!("{}", map));
+    while !resumed.all_flashed() {
+        resumed.step();
+    }
+    assert_eq!(resumed.steps, 195);
+}
+
 #[test]
 fn test_step() {
     let input = "
@@ -266,7 +328,7 @@ fn test_step() {
 34543
 "
     .trim();
-    let mut map = parse(&input);
+    let mut map = parse(input);
     let flash_count = map.step();
     assert_eq!(flash_count, 9);
     assert_eq!(format!("{}", map).trim(), step_1);
@@ -301,8 +363,34 @@ fn test_step_large() {
 6394862637
 "
     .trim();
-    let mut map = parse(&input);
+    let mut map = parse(input);
     let flash_count = map.step();
     assert_eq!(flash_count, 0);
     assert_eq!(format!("{}", map).trim(), step_1);
 }
+
+#[test]
+fn test_step_on_torus() {
+    let input = "
+900
+000
+000
+"
+    .trim();
+    // The flash in the corner reaches the opposite edges
+    let mut map = Map::new(parse_octopi(input).unwrap().with_topology(Topology::Torus));
+    assert_eq!(map.step(), 1);
+    assert_eq!(format!("{}", map), "022\n222\n222\n");
+    let resumed: Map = checkpoint::from_str(&checkpoint::to_string(&map)).unwrap();
+    assert!(matches!(resumed.octopi.topology(), Topology::Torus));
+}
+
+#[test]
+fn test_cycle_after_synchronizing() {
+    let map = parse(include_str!("../resources/day11_small.txt"));
+    // Once they all flashed together, they do it again every 10 steps
+    let cycle = simulation::find_cycle(&map, 1000).unwrap().unwrap();
+    assert_eq!((cycle.start, cycle.period), (195, 10));
+    let far = simulation::fast_forward(&map, 1_000_000_000, 1000).unwrap();
+    assert_eq!(format!("{}", far), "5555555555\n".repeat(10));
+}
@@ -1,111 +1,82 @@
+use crate::graph::{Graph, NodeId};
+use crate::perf;
 use crate::util;
-use std::collections::HashSet;
 
 pub fn day_12() -> Result<(), String> {
-    //let contents = util::read_file("./resources/day12_small.txt").expect("Could not open file");
-    //let contents = util::read_file("./resources/day12_smallest.txt").expect("Could not open file");
-    let contents = util::read_file("./resources/day12.txt").expect("Could not open file");
+    //let contents = util::read_input("day12_small.txt").expect("Could not open file");
+    //let contents = util::read_input("day12_smallest.txt").expect("Could not open file");
+    let contents = util::read_input("day12.txt").expect("Could not open file");
 
     println!("contents: \n{}", contents);
 
-    let edges: Vec<Edge<String>> = contents
-        .trim()
-        .split('\n')
-        .map(|v| v.try_into().unwrap())
-        .collect();
+    let caves = Caves::parse(&contents)?;
+    println!(
+        "caves {:?}",
+        (0..caves.graph.len())
+            .map(|id| caves.graph.node(id))
+            .collect::<Vec<_>>()
+    );
 
-    let nodes: Vec<Node<String>> = contents
-        .trim()
-        .split('\n')
-        .flat_map(|v| v.split('-'))
-        .map(|v| v.into())
-        .collect::<HashSet<Node<String>>>()
-        .into_iter()
-        .collect();
+    let distances = caves.graph.bfs(caves.start);
+    let shortest = distances[caves.end].ok_or("end cannot be reached from start")?;
+    println!("shortest path from start to end: {} tunnels", shortest);
 
-    println!("edges {:?}", edges);
-    println!("nodes {:?}", nodes);
-    let g = Graph::new(nodes, edges);
-
-    let mut seen: HashSet<String> = HashSet::new();
-    let result = dfs_part1(&g, "start".to_string(), seen.clone());
+    let seen = vec![false; caves.graph.len()];
+    let result = perf::phase("dfs_part1", || dfs_part1(&caves, caves.start, seen.clone()));
     println!("result part 1: {:?}", result);
-    let result = dfs_part2(&g, "start".to_string(), seen.clone(), None);
+    let result = perf::phase("dfs_part2", || {
+        dfs_part2(&caves, caves.start, seen.clone(), None)
+    });
     println!("result part 2: {:?}", result);
     Ok(())
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
-struct Node<T>(T);
-
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
-struct Edge<T>(T, T);
-
-struct Graph<T> {
-    nodes: Vec<Node<T>>,
-    edges: Vec<Edge<T>>,
+struct Caves {
+    graph: Graph<String>,
+    // Small caves, named in lower case, can only be visited once
+    small: Vec<bool>,
+    start: NodeId,
+    end: NodeId,
 }
 
-impl Graph<String> {
-    fn new(nodes: Vec<Node<String>>, edges: Vec<Edge<String>>) -> Self {
-        Self { nodes, edges }
-    }
-
-    fn neighbors(&self, node: Node<String>) -> Vec<Node<String>> {
-        let neighbors = self
-            .edges
-            .iter()
-            .filter(|e| e.0 == node.0)
-            .map(|e| e.1.clone().into());
-        let reverse_neighbors = self
-            .edges
-            .iter()
-            .filter(|e| e.1 == node.0)
-            .map(|e| e.0.clone().into());
-        neighbors.chain(reverse_neighbors).collect()
-    }
-}
-
-impl From<&str> for Node<String> {
-    fn from(s: &str) -> Self {
-        Node(s.to_string())
-    }
-}
-impl From<String> for Node<String> {
-    fn from(s: String) -> Self {
-        Node(s)
-    }
-}
-
-impl TryFrom<&str> for Edge<String> {
-    type Error = String;
-
-    fn try_from(value: &str) -> Result<Self, Self::Error> {
-        match value.split_once('-') {
-            Some((s1, s2)) => Ok(Edge(s1.to_string(), s2.to_string())),
-            None => Err("Cannot parse value".to_string()),
+impl Caves {
+    fn parse(contents: &str) -> Result<Self, String> {
+        let mut graph = Graph::undirected();
+        for line in contents.trim().split('\n') {
+            match line.trim().split_once('-') {
+                Some((s1, s2)) => graph.add_edge(s1.to_string(), s2.to_string()),
+                None => return Err(format!("Cannot parse value: {}", line)),
+            };
         }
+        let small = (0..graph.len())
+            .map(|id| graph.node(id).to_lowercase() == *graph.node(id))
+            .collect();
+        Ok(Caves {
+            start: graph.id("start").ok_or("missing start")?,
+            end: graph.id("end").ok_or("missing end")?,
+            small,
+            graph,
+        })
     }
 }
 
-fn dfs_part1(graph: &Graph<String>, current: String, mut seen: HashSet<String>) -> Option<u32> {
-    if current == "end" {
+fn dfs_part1(caves: &Caves, current: NodeId, mut seen: Vec<bool>) -> Option<u32> {
+    if current == caves.end {
         //println!("reached end: \n seen: {:?}", seen);
         return Some(1);
     }
 
-    // If current is lower, add it to the seen hash.
+    // If current is a small cave, mark it as seen.
     //println!("in {}", current);
-    if current.to_lowercase() == current {
-        seen.insert(current.clone());
+    if caves.small[current] {
+        seen[current] = true;
     }
-    let current_node = Node(current.clone());
     let mut total = 0;
-    for n in graph.neighbors(current_node) {
-        if seen.contains(&n.0) {
+    for n in caves.graph.neighbors(current) {
+        if seen[n] {
             continue;
         }
-        if let Some(count) = dfs_part1(graph, n.0.clone(), seen.clone()) {
+        if let Some(count) = dfs_part1(caves, n, seen.clone()) {
             total += count;
         }
     }
@@ -114,40 +85,51 @@ fn dfs_part1(graph: &Graph<String>, current: String, mut seen: HashSet<String>)
 }
 
 fn dfs_part2(
-    graph: &Graph<String>,
-    current: String,
-    mut seen: HashSet<String>,
-    mut extra: Option<String>,
+    caves: &Caves,
+    current: NodeId,
+    mut seen: Vec<bool>,
+    extra: Option<NodeId>,
 ) -> Option<u32> {
-    if current == "end" {
+    if current == caves.end {
         //println!("reached end: \n seen: {:?}", seen);
         return Some(1);
     }
-    let start = String::from("start");
 
-    // If current is lower, add it to the seen hash.
+    // If current is a small cave, mark it as seen.
     //println!("in {}, extra: {:?}", current, extra);
-    if current.to_lowercase() == current {
-        seen.insert(current.clone());
+    if caves.small[current] {
+        seen[current] = true;
     }
-    let current_node = Node(current.clone());
     let mut total = 0;
-    for n in graph.neighbors(current_node) {
-        if seen.contains(&n.0) && extra.is_none() {
-            if start.eq(&n.0) {
+    for n in caves.graph.neighbors(current) {
+        if seen[n] && extra.is_none() {
+            if n == caves.start {
                 // Don't visit start twice.
                 continue;
             }
             // allow small cave twice only once!
-            if let Some(count) = dfs_part1(graph, n.0.clone(), seen.clone()) {
+            if let Some(count) = dfs_part1(caves, n, seen.clone()) {
                 total += count;
             }
             continue;
         }
-        if let Some(count) = dfs_part2(graph, n.0.clone(), seen.clone(), extra.clone()) {
+        if let Some(count) = dfs_part2(caves, n, seen.clone(), extra) {
             total += count;
         }
     }
 
     Some(total)
 }
+
+#[test]
+fn count_paths_small_examples() {
+    for (contents, part1, part2) in [
+        (include_str!("../resources/day12_smallest.txt"), 10, 36),
+        (include_str!("../resources/day12_small.txt"), 226, 3509),
+    ] {
+        let caves = Caves::parse(contents).unwrap();
+        let seen = vec![false; caves.graph.len()];
+        assert_eq!(dfs_part1(&caves, caves.start, seen.clone()), Some(part1));
+        assert_eq!(dfs_part2(&caves, caves.start, seen, None), Some(part2));
+    }
+}
@@ -1,6 +1,9 @@
 use std::error::Error;
 use std::{fmt, vec};
 
+use crate::animate::{Animation, Frame};
+use crate::grid::{Bounds, Grid, SparseGrid};
+use crate::point::Point;
 use crate::util;
 
 #[derive(Debug)]
@@ -37,25 +40,22 @@ struct Fold {
 
 #[derive(Debug)]
 struct Paper {
-    dots: Vec<Vec<bool>>,
+    // Only the dots are stored, the sheet can be much larger
+    dots: SparseGrid<bool>,
     width: usize,
     height: usize,
 }
 
 impl fmt::Display for Paper {
-    // This trait requires `fmt` with this exact signature.
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        // Write strictly the first element into the supplied output
-        // stream: `f`. Returns `fmt::Result` which indicates whether the
-        // operation succeeded or failed. Note that `write!` uses syntax which
-        // is very similar to `println!`.
-        self.dots.iter().for_each(|r| {
-            r.iter().for_each(|bn| {
-                let _ = write!(f, "{}", if *bn { "#" } else { "." });
-            });
-            let _ = writeln!(f);
-        });
-        write!(f, "")
+        for y in 0..self.height as i64 {
+            for x in 0..self.width as i64 {
+                let dot = self.dots.get(Point::new(x, y)).is_some();
+                write!(f, "{}", if dot { '#' } else { '.' })?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
     }
 }
 
@@ -63,8 +63,8 @@ type Folds = Vec<Fold>;
 type Result<T> = std::result::Result<T, Box<dyn Error>>;
 
 pub fn day_13() -> Result<()> {
-    //let contents = util::read_file("./resources/day13_small.txt").expect("Could not open file");
-    let contents = util::read_file("./resources/day13.txt").expect("Could not open file");
+    //let contents = util::read_input("day13_small.txt").expect("Could not open file");
+    let contents = util::read_input("day13.txt").expect("Could not open file");
 
     let (paper, folds) = parse(&contents)?;
     println!("folds:\n{:?}", folds);
@@ -75,6 +75,33 @@ pub fn day_13() -> Result<()> {
     let final_paper = folds.iter().fold(paper, |p, f| apply_fold(&p, f).unwrap());
 
     println!("Paper (part 2):\n{}", final_paper);
+    println!("Letters: {}", final_paper.count_letters());
+    Ok(())
+}
+
+// The paper before any fold, then after each fold in order.
+pub(crate) fn frames(contents: &str) -> Result<Vec<Frame>> {
+    let (paper, folds) = parse(contents)?;
+    let mut frames = vec![Frame {
+        caption: format!("unfolded, dots: {}", paper.count_dots()),
+        body: format!("{}", paper),
+        highlight: '#',
+    }];
+    let mut paper = paper;
+    for (i, fold) in folds.iter().enumerate() {
+        paper = apply_fold(&paper, fold)?;
+        frames.push(Frame {
+            caption: format!("fold {}: {:?}, dots: {}", i + 1, fold, paper.count_dots()),
+            body: format!("{}", paper),
+            highlight: '#',
+        });
+    }
+    Ok(frames)
+}
+
+pub fn animate(animation: &Animation) -> Result<()> {
+    let contents = util::read_input("day13.txt")?;
+    animation.play(frames(&contents)?)?;
     Ok(())
 }
 
@@ -89,31 +116,45 @@ fn parse(contents: &str) -> Result<(Paper, Folds)> {
 
 impl Paper {
     fn parse(contents: &str) -> Result<Self> {
-        let dots: Vec<(usize, usize)> = contents
-            .trim()
-            .split('\n')
-            .map(|line| line.split_once(',').unwrap())
-            .map(|(str_x, str_y)| (str_x.parse().unwrap(), str_y.parse().unwrap()))
-            .collect();
-        let width = *dots.iter().map(|(x, _y)| x).max().expect("No data in dots") + 1;
-        let height = *dots.iter().map(|(_x, y)| y).max().expect("No data in dots") + 1;
-        let mut dot_array = vec![vec![false; width]; height];
-        for (x, y) in dots.into_iter() {
-            dot_array[y][x] = true;
+        let mut dots = SparseGrid::new();
+        for line in contents.trim().split('\n') {
+            let pos: Point<usize> = line.parse()?;
+            dots.insert(pos.try_cast().ok_or("dot is too far")?, true);
         }
+        let bounds = dots.bounds().expect("No data in dots");
 
         Ok(Paper {
-            dots: dot_array,
-            width,
-            height,
+            width: bounds.max.x as usize + 1,
+            height: bounds.max.y as usize + 1,
+            dots,
         })
     }
 
     fn count_dots(&self) -> usize {
-        self.dots
-            .iter()
-            .map(|row| row.iter().filter(|b| **b).count())
-            .sum()
+        self.dots.len()
+    }
+
+    fn sheet(&self) -> Grid<bool> {
+        let sheet = Bounds::new(
+            Point::new(0, 0),
+            Point::new(self.width as i64 - 1, self.height as i64 - 1),
+        );
+        self.dots.to_dense(sheet, false)
+    }
+
+    // Letters of the code are separated by columns without any dot
+    fn count_letters(&self) -> usize {
+        let sheet = self.sheet();
+        let mut letters = 0;
+        let mut in_letter = false;
+        for x in 0..sheet.width() {
+            let has_dot = sheet.column(x).any(|dot| *dot);
+            if has_dot && !in_letter {
+                letters += 1;
+            }
+            in_letter = has_dot;
+        }
+        letters
     }
 }
 
@@ -147,27 +188,25 @@ fn apply_fold(paper: &Paper, fold: &Fold) -> Result<Paper> {
         FoldDirection::Vertical => fold.position,
     };
 
-    let mut new_dot_array = vec![vec![false; new_width]; new_height];
-    paper.dots.iter().enumerate().for_each(|(row, r)| {
-        r.iter().enumerate().for_each(|(col, dot)| {
-            let adjusted_col = if col > new_width - 1 {
-                fold.position - (col - fold.position)
-            } else {
-                col
-            };
-            let adjusted_row = if row > new_height - 1 {
-                fold.position - (row - fold.position)
-            } else {
-                row
-            };
-            if adjusted_row < new_height && adjusted_col < new_width {
-                new_dot_array[adjusted_row][adjusted_col] |= *dot;
-            }
-        });
-    });
+    // Dots past the fold are mirrored, the ones on the fold line disappear
+    let fold_position = fold.position as i64;
+    let mirror = |c: i64, size: usize| {
+        if c > size as i64 - 1 {
+            fold_position - (c - fold_position)
+        } else {
+            c
+        }
+    };
+    let mut new_dots = SparseGrid::new();
+    for (pos, _) in paper.dots.iter() {
+        let adjusted = Point::new(mirror(pos.x, new_width), mirror(pos.y, new_height));
+        if adjusted.x < new_width as i64 && adjusted.y < new_height as i64 {
+            new_dots.insert(adjusted, true);
+        }
+    }
 
     Ok(Paper {
-        dots: new_dot_array,
+        dots: new_dots,
         height: new_height,
         width: new_width,
     })
@@ -217,4 +256,8 @@ fold along x=5
         format!("{}", paper_folded_once).trim(),
         expected_folded_once
     );
+    // Columns 5 and 7 have no dot
+    assert_eq!(paper_folded_once.count_letters(), 3);
+    let square = apply_fold(&paper_folded_once, &folds[1]).unwrap();
+    assert_eq!(square.count_letters(), 1);
 }
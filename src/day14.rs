@@ -1,8 +1,10 @@
-use crate::util::Result;
+use crate::checkpoint::{self, Checkpoint, Session};
+use crate::perf;
+use crate::simulation::{self, Simulation};
+use crate::util::{self, Result};
 
 use std::collections::HashMap;
-use std::convert::TryInto;
-use std::usize::MAX;
+use std::fmt;
 
 type Pair = (u8, u8);
 
@@ -17,17 +19,58 @@ struct Rules {
     map: HashMap<Pair, u8>,
 }
 
-pub fn day_14() -> Result<()> {
-    let contents = include_str!("../resources/day14.txt");
-    let (mut polymer, rules) = parse(contents)?;
-    for _ in 0..10 {
-        polymer = step(&polymer, &rules).unwrap();
+// A polymer growing with the insertion rules
+struct Reaction<'a> {
+    polymer: Polymer,
+    rules: &'a Rules,
+}
+
+impl Simulation for Reaction<'_> {
+    type Fingerprint = Vec<(Pair, usize)>;
+
+    fn step(&mut self) -> Result<()> {
+        self.polymer = step(&self.polymer, self.rules)?;
+        Ok(())
     }
-    println!("Part 1: {:?}", diff_most_and_least_common_elem(&polymer));
-    for _ in 10..40 {
-        polymer = step(&polymer, &rules).unwrap();
+
+    fn fingerprint(&self) -> Self::Fingerprint {
+        let mut pairs: Vec<_> = self.polymer.map.iter().map(|(p, c)| (*p, *c)).collect();
+        pairs.sort();
+        pairs
+    }
+}
+
+impl fmt::Display for Reaction<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} pairs, most minus least common element: ",
+            self.polymer.map.len()
+        )?;
+        match diff_most_and_least_common_elem(&self.polymer) {
+            Ok(diff) => write!(f, "{}", diff),
+            Err(e) => write!(f, "{}", e),
+        }
     }
-    println!("Part 2: {:?}", diff_most_and_least_common_elem(&polymer));
+}
+
+pub fn day_14() -> Result<()> {
+    let contents = util::read_input("day14.txt")?;
+    let (polymer, rules) = parse(&contents)?;
+    let mut reaction = Reaction {
+        polymer,
+        rules: &rules,
+    };
+    perf::phase("part1", || simulation::run(&mut reaction, 10))?;
+    println!(
+        "Part 1: {:?}",
+        diff_most_and_least_common_elem(&reaction.polymer)?
+    );
+    perf::phase("part2", || simulation::run(&mut reaction, 30))?;
+    println!(
+        "Part 2: {:?}",
+        diff_most_and_least_common_elem(&reaction.polymer)?
+    );
     Ok(())
 }
 
@@ -38,26 +81,32 @@ fn parse(contents: &str) -> Result<(Polymer, Rules)> {
     Ok((polymer, rules))
 }
 
-fn count_elements(polymer: &Polymer) -> HashMap<u8, usize> {
+fn count_elements(polymer: &Polymer) -> Result<HashMap<u8, usize>> {
     // Take the first element of each "pairs", since they are forming a long
     // link. Add the last element at the end, since it's not the start of a pair
     let last_elem = polymer.last_elem;
     let mut elem_count: HashMap<u8, usize> = HashMap::new();
 
     for (pair, count) in polymer.map.iter() {
-        elem_count
-            .entry(pair.0)
-            .and_modify(|c| *c += count)
-            .or_insert(*count);
+        checked_add(elem_count.entry(pair.0).or_insert(0), *count)?;
     }
 
-    elem_count.entry(last_elem).and_modify(|count| *count += 1);
-    elem_count
+    if let Some(count) = elem_count.get_mut(&last_elem) {
+        checked_add(count, 1)?;
+    }
+    Ok(elem_count)
 }
 
-fn diff_most_and_least_common_elem(polymer: &Polymer) -> usize {
-    let counted_elem = count_elements(polymer);
-    let mut min = MAX;
+fn checked_add(counter: &mut usize, times: usize) -> Result<()> {
+    *counter = counter
+        .checked_add(times)
+        .ok_or("count overflows, the polymer is too long")?;
+    Ok(())
+}
+
+fn diff_most_and_least_common_elem(polymer: &Polymer) -> Result<usize> {
+    let counted_elem = count_elements(polymer)?;
+    let mut min = usize::MAX;
     let mut max = 0usize;
     for (_, count) in counted_elem.into_iter() {
         if count > max {
@@ -67,7 +116,7 @@ fn diff_most_and_least_common_elem(polymer: &Polymer) -> usize {
             min = count;
         }
     }
-    max - min
+    Ok(max - min)
 }
 
 impl Rules {
@@ -75,7 +124,6 @@ impl Rules {
         let hashmap = input
             .trim()
             .split('\n')
-            .into_iter()
             .map(|line| {
                 let (adjacent_elem, out_elem) = line.split_once(" -> ").unwrap();
                 assert!(adjacent_elem.len() >= 2);
@@ -112,20 +160,69 @@ impl Polymer {
         let last_elem = *content.as_bytes().last().unwrap();
         let mut polymer = Self::new(last_elem);
         for pair in map_tuple {
-            polymer.add(&pair, 1);
+            polymer.add(&pair, 1)?;
         }
 
         Ok(polymer)
     }
 
-    fn add(&mut self, pair: &Pair, times: usize) {
-        self.map
-            .entry(*pair)
-            .and_modify(|counter| *counter += times)
-            .or_insert(times);
+    fn add(&mut self, pair: &Pair, times: usize) -> Result<()> {
+        checked_add(self.map.entry(*pair).or_insert(0), times)
     }
 }
 
+impl Checkpoint for Polymer {
+    const KIND: &'static str = "polymer";
+
+    fn to_lines(&self) -> Vec<String> {
+        let mut pairs: Vec<_> = self.map.iter().collect();
+        pairs.sort();
+        let mut lines = vec![format!("last_elem {}", self.last_elem as char)];
+        lines.extend(
+            pairs.into_iter().map(|(pair, count)| {
+                format!("pair {}{} {}", pair.0 as char, pair.1 as char, count)
+            }),
+        );
+        lines
+    }
+
+    fn from_lines(lines: &[&str]) -> Result<Self> {
+        let last_elem = checkpoint::field(lines.first(), "last_elem")?;
+        let last_elem = match last_elem.as_slice() {
+            [elem] if elem.len() == 1 => elem.as_bytes()[0],
+            _ => return Err("expected a single last element".into()),
+        };
+        let mut polymer = Polymer::new(last_elem);
+        for line in lines[1..].iter() {
+            match checkpoint::field(Some(line), "pair")?.as_slice() {
+                [pair, count] if pair.len() == 2 => {
+                    let pair = pair.as_bytes();
+                    polymer.add(&(pair[0], pair[1]), count.parse()?)?;
+                }
+                _ => return Err(format!("invalid pair: {}", line).into()),
+            }
+        }
+        Ok(polymer)
+    }
+}
+
+pub fn simulate(session: &Session) -> Result<()> {
+    let contents = util::read_input("day14.txt")?;
+    let (polymer, rules) = parse(&contents)?;
+    let polymer = session.run(
+        || Ok(polymer),
+        |polymer| {
+            *polymer = step(polymer, &rules)?;
+            Ok(())
+        },
+    )?;
+    println!(
+        "Most minus least common element: {}",
+        diff_most_and_least_common_elem(&polymer)?
+    );
+    Ok(())
+}
+
 fn step(polymer: &Polymer, rules: &Rules) -> Result<Polymer> {
     // Every pair produce 2 new pairs according to the rules and produce a new
     // polymer.
@@ -134,8 +231,8 @@ fn step(polymer: &Polymer, rules: &Rules) -> Result<Polymer> {
     for (pair, count) in polymer.map.iter() {
         let maybe_pairs = rules.apply(pair);
         if let Some(pairs) = maybe_pairs {
-            new_polymer.add(&pairs.0, *count);
-            new_polymer.add(&pairs.1, *count);
+            new_polymer.add(&pairs.0, *count)?;
+            new_polymer.add(&pairs.1, *count)?;
         }
     }
 
@@ -170,7 +267,7 @@ fn apply_4_steps() {
 fn count_polymer() {
     let polymer = Polymer::parse("NNCB").unwrap();
 
-    let count = count_elements(&polymer);
+    let count = count_elements(&polymer).unwrap();
 
     let n = 'N'.try_into().unwrap();
     assert_eq!(*count.get(&n).unwrap(), 2usize)
@@ -183,7 +280,44 @@ fn apply_10_steps() {
     for _ in 0..10 {
         polymer = step(&polymer, &rules).unwrap();
     }
-    let diff = diff_most_and_least_common_elem(&polymer);
+    let diff = diff_most_and_least_common_elem(&polymer).unwrap();
 
     assert_eq!(diff, 1588);
 }
+
+#[test]
+fn checkpoint_resume() {
+    let contents = include_str!("../resources/day14_small.txt");
+    let (mut polymer, rules) = parse(contents).unwrap();
+    for _ in 0..4 {
+        polymer = step(&polymer, &rules).unwrap();
+    }
+    let saved = checkpoint::to_string(&polymer);
+    let mut resumed: Polymer = checkpoint::from_str(&saved).unwrap();
+    assert_eq!(resumed, polymer);
+    for _ in 4..10 {
+        resumed = step(&resumed, &rules).unwrap();
+    }
+    assert_eq!(diff_most_and_least_common_elem(&resumed).unwrap(), 1588);
+}
+
+#[test]
+fn long_run_fails_instead_of_overflowing() {
+    let contents = include_str!("../resources/day14_small.txt");
+    let (polymer, rules) = parse(contents).unwrap();
+    let session = Session {
+        steps: 1000,
+        ..Default::default()
+    };
+    let err = session
+        .run(
+            || Ok(polymer),
+            |polymer| {
+                *polymer = step(polymer, &rules)?;
+                Ok(())
+            },
+        )
+        .err()
+        .unwrap();
+    assert_eq!(err.to_string(), "count overflows, the polymer is too long");
+}
@@ -0,0 +1,46 @@
+use std::fmt;
+
+// The steps a solution went through to reach its answer. Days push their own
+// event type, and nothing is kept unless an explanation was asked for.
+pub(crate) struct Trace<E> {
+    events: Option<Vec<E>>,
+}
+
+impl<E> Trace<E> {
+    pub(crate) fn new() -> Self {
+        Trace {
+            events: Some(Vec::new()),
+        }
+    }
+
+    pub(crate) fn disabled() -> Self {
+        Trace { events: None }
+    }
+
+    pub(crate) fn push(&mut self, event: E) {
+        if let Some(events) = self.events.as_mut() {
+            events.push(event);
+        }
+    }
+
+    // Same as `push`, for events that are costly to build: `event` is only
+    // called when the trace is kept
+    pub(crate) fn push_with(&mut self, event: impl FnOnce() -> E) {
+        if let Some(events) = self.events.as_mut() {
+            events.push(event());
+        }
+    }
+
+    pub(crate) fn events(&self) -> &[E] {
+        self.events.as_deref().unwrap_or_default()
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for Trace<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, event) in self.events().iter().enumerate() {
+            writeln!(f, "{:>4}. {}", i + 1, event)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,298 @@
+// A general purpose graph library: only some of the traversals are used by
+// a day so far, the others are covered by the tests.
+#![allow(dead_code)]
+
+use crate::grid::Grid;
+use crate::point::Point;
+use crate::union_find::UnionFind;
+
+use std::borrow::Borrow;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::hash::Hash;
+
+// Index of a node, in the order the nodes were added
+pub(crate) type NodeId = usize;
+
+// Nodes of any hashable type, interned to ids, with their edges kept as
+// adjacency lists. Edges of unweighted graphs have a weight of 1.
+#[derive(Debug, Clone)]
+pub(crate) struct Graph<N> {
+    nodes: Vec<N>,
+    ids: HashMap<N, NodeId>,
+    outgoing: Vec<Vec<(NodeId, u64)>>,
+    directed: bool,
+}
+
+impl<N: Clone + Eq + Hash> Graph<N> {
+    pub(crate) fn directed() -> Self {
+        Self::new(true)
+    }
+
+    pub(crate) fn undirected() -> Self {
+        Self::new(false)
+    }
+
+    fn new(directed: bool) -> Self {
+        Graph {
+            nodes: Vec::new(),
+            ids: HashMap::new(),
+            outgoing: Vec::new(),
+            directed,
+        }
+    }
+
+    // Id of the node, adding it if it is new
+    pub(crate) fn intern(&mut self, node: N) -> NodeId {
+        if let Some(&id) = self.ids.get(&node) {
+            return id;
+        }
+        let id = self.nodes.len();
+        self.nodes.push(node.clone());
+        self.ids.insert(node, id);
+        self.outgoing.push(Vec::new());
+        id
+    }
+
+    pub(crate) fn add_edge(&mut self, from: N, to: N) -> (NodeId, NodeId) {
+        self.add_weighted_edge(from, to, 1)
+    }
+
+    pub(crate) fn add_weighted_edge(&mut self, from: N, to: N, weight: u64) -> (NodeId, NodeId) {
+        let (from, to) = (self.intern(from), self.intern(to));
+        self.outgoing[from].push((to, weight));
+        if !self.directed && from != to {
+            self.outgoing[to].push((from, weight));
+        }
+        (from, to)
+    }
+
+    pub(crate) fn id<Q>(&self, node: &Q) -> Option<NodeId>
+    where
+        N: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.ids.get(node).copied()
+    }
+}
+
+impl<N> Graph<N> {
+    pub(crate) fn node(&self, id: NodeId) -> &N {
+        &self.nodes[id]
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub(crate) fn neighbors(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        self.outgoing[id].iter().map(|&(to, _)| to)
+    }
+}
+
+impl<N> Graph<N> {
+    // Nodes reachable by one edge, with the weight of the edge
+    pub(crate) fn edges(&self, id: NodeId) -> &[(NodeId, u64)] {
+        &self.outgoing[id]
+    }
+
+    // Number of edges from `start` to every node, `None` if it can't be reached
+    pub(crate) fn bfs(&self, start: NodeId) -> Vec<Option<usize>> {
+        let mut distances = vec![None; self.len()];
+        let mut queue = VecDeque::from([start]);
+        distances[start] = Some(0);
+        while let Some(id) = queue.pop_front() {
+            let next = distances[id].map(|d| d + 1);
+            for to in self.neighbors(id) {
+                if distances[to].is_none() {
+                    distances[to] = next;
+                    queue.push_back(to);
+                }
+            }
+        }
+        distances
+    }
+
+    // Nodes reachable from `start`, in depth first order
+    pub(crate) fn dfs(&self, start: NodeId) -> Vec<NodeId> {
+        let mut seen = vec![false; self.len()];
+        let mut order = Vec::new();
+        let mut stack = vec![start];
+        while let Some(id) = stack.pop() {
+            if std::mem::replace(&mut seen[id], true) {
+                continue;
+            }
+            order.push(id);
+            // Reversed so neighbors are visited in the order they were added
+            stack.extend(
+                self.neighbors(id)
+                    .filter(|&to| !seen[to])
+                    .collect::<Vec<_>>()
+                    .into_iter()
+                    .rev(),
+            );
+        }
+        order
+    }
+
+    // Cost of the cheapest path from `start` to every node
+    pub(crate) fn dijkstra(&self, start: NodeId) -> Vec<Option<u64>> {
+        let mut costs = vec![None; self.len()];
+        let mut queue = BinaryHeap::from([Reverse((0, start))]);
+        while let Some(Reverse((cost, id))) = queue.pop() {
+            if costs[id].is_some() {
+                continue;
+            }
+            costs[id] = Some(cost);
+            for &(to, weight) in self.edges(id) {
+                if costs[to].is_none() {
+                    queue.push(Reverse((cost + weight, to)));
+                }
+            }
+        }
+        costs
+    }
+
+    // Cheapest path from `start` to `goal` and its cost. `heuristic` must
+    // never overestimate the cost left to reach the goal.
+    pub(crate) fn astar(
+        &self,
+        start: NodeId,
+        goal: NodeId,
+        heuristic: impl Fn(NodeId) -> u64,
+    ) -> Option<(u64, Vec<NodeId>)> {
+        let mut best = vec![u64::MAX; self.len()];
+        let mut previous = vec![None; self.len()];
+        let mut queue = BinaryHeap::from([Reverse((heuristic(start), 0, start))]);
+        best[start] = 0;
+        while let Some(Reverse((_, cost, id))) = queue.pop() {
+            if id == goal {
+                let mut path = vec![goal];
+                while let Some(p) = previous[*path.last().unwrap()] {
+                    path.push(p);
+                }
+                path.reverse();
+                return Some((cost, path));
+            }
+            if cost > best[id] {
+                continue;
+            }
+            for &(to, weight) in self.edges(id) {
+                let next = cost + weight;
+                if next < best[to] {
+                    best[to] = next;
+                    previous[to] = Some(id);
+                    queue.push(Reverse((next + heuristic(to), next, to)));
+                }
+            }
+        }
+        None
+    }
+
+    // Nodes ordered so every edge goes forward, `None` if there is a cycle.
+    // Only meaningful for directed graphs.
+    pub(crate) fn topological_sort(&self) -> Option<Vec<NodeId>> {
+        let mut in_degree = vec![0; self.len()];
+        for id in 0..self.len() {
+            for to in self.neighbors(id) {
+                in_degree[to] += 1;
+            }
+        }
+        let mut ready: VecDeque<NodeId> =
+            (0..self.len()).filter(|&id| in_degree[id] == 0).collect();
+        let mut order = Vec::with_capacity(self.len());
+        while let Some(id) = ready.pop_front() {
+            order.push(id);
+            for to in self.neighbors(id) {
+                in_degree[to] -= 1;
+                if in_degree[to] == 0 {
+                    ready.push_back(to);
+                }
+            }
+        }
+        (order.len() == self.len()).then_some(order)
+    }
+
+    // Groups of nodes linked by edges, ignoring their direction
+    pub(crate) fn components(&self) -> Vec<Vec<NodeId>> {
+        let mut sets = UnionFind::new(self.len());
+        for id in 0..self.len() {
+            for to in self.neighbors(id) {
+                sets.union(id, to);
+            }
+        }
+        // Numbered in the order of their smallest node
+        let mut index_of_root = vec![None; self.len()];
+        let mut components: Vec<Vec<NodeId>> = Vec::new();
+        for id in 0..self.len() {
+            let root = sets.find(id);
+            let index = *index_of_root[root].get_or_insert_with(|| {
+                components.push(Vec::new());
+                components.len() - 1
+            });
+            components[index].push(id);
+        }
+        components
+    }
+}
+
+// Directed graph of the cells of a grid, linked to their 4 neighbors. `weight`
+// gives the cost of moving between two cells, or `None` if it is not allowed.
+pub(crate) fn from_grid<T>(
+    grid: &Grid<T>,
+    weight: impl Fn(&T, &T) -> Option<u64>,
+) -> Graph<Point<usize>> {
+    let mut graph = Graph::directed();
+    for pos in grid.positions() {
+        graph.intern(pos);
+        for to in grid.neighbors4(pos) {
+            if let Some(w) = weight(&grid[pos], &grid[to]) {
+                graph.add_weighted_edge(pos, to, w);
+            }
+        }
+    }
+    graph
+}
+
+#[test]
+fn traversals() {
+    let mut graph = Graph::directed();
+    for (from, to) in [
+        ("shirt", "tie"),
+        ("tie", "jacket"),
+        ("belt", "jacket"),
+        ("socks", "shoes"),
+    ] {
+        graph.add_edge(from, to);
+    }
+    let id = |name| graph.id(name).unwrap();
+    assert_eq!(graph.bfs(id("shirt"))[id("jacket")], Some(2));
+    assert_eq!(
+        graph.dfs(id("shirt")),
+        vec![id("shirt"), id("tie"), id("jacket")]
+    );
+    let order = graph.topological_sort().unwrap();
+    let position = |name| order.iter().position(|&n| n == id(name)).unwrap();
+    assert!(position("belt") < position("jacket") && position("tie") < position("jacket"));
+    assert_eq!(graph.components().len(), 2);
+
+    graph.add_edge("jacket", "shirt");
+    assert_eq!(graph.topological_sort(), None);
+}
+
+#[test]
+fn shortest_paths_on_grid() {
+    // Entering a cell costs its value
+    let grid: Grid<u64> = Grid::parse_digits("1163\n1381\n2136\n").unwrap();
+    let graph = from_grid(&grid, |_, to| Some(*to));
+    let start = graph.id(&Point::new(0, 0)).unwrap();
+    let goal = graph.id(&Point::new(3, 2)).unwrap();
+    assert_eq!(graph.dijkstra(start)[goal], Some(13));
+    let (cost, path) = graph
+        .astar(start, goal, |id| {
+            graph.node(id).manhattan(Point::new(3, 2)) as u64
+        })
+        .unwrap();
+    assert_eq!(cost, 13);
+    assert_eq!((path[0], *path.last().unwrap()), (start, goal));
+}
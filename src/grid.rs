@@ -0,0 +1,500 @@
+use crate::point::Point;
+use crate::union_find::UnionFind;
+use crate::util::Result;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
+const ORTHOGONAL: &[Point<i64>] = &Point::<i64>::ORTHOGONAL;
+const ALL_DIRECTIONS: &[Point<i64>] = &Point::<i64>::ALL_DIRECTIONS;
+
+// What lies past the edges of a grid
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Topology<T> {
+    // Nothing, cells on the edges have fewer neighbors
+    Bounded,
+    // The opposite edge, as on a torus
+    Torus,
+    // Cells that all hold this value and never change
+    Infinite(T),
+}
+
+// A rectangle of cells stored row by row, addressed with a point where `x`
+// is the column and `y` the row.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+    topology: Topology<T>,
+}
+
+impl<T: Clone> Grid<T> {
+    pub(crate) fn filled(width: usize, height: usize, value: T) -> Self {
+        Grid {
+            width,
+            height,
+            cells: vec![value; width * height],
+            topology: Topology::Bounded,
+        }
+    }
+}
+
+impl<T> Grid<T> {
+    // Parse one cell per character, one row per line. All rows must have
+    // the same length.
+    pub(crate) fn parse_with(input: &str, mut cell: impl FnMut(char) -> Result<T>) -> Result<Self> {
+        let mut width = None;
+        let mut height = 0;
+        let mut cells = Vec::new();
+        for (y, line) in input.trim().lines().enumerate() {
+            let line = line.trim();
+            if *width.get_or_insert(line.len()) != line.len() {
+                return Err(format!("line {}: expected {} cells", y + 1, width.unwrap()).into());
+            }
+            for c in line.chars() {
+                cells.push(cell(c).map_err(|e| format!("line {}: {}", y + 1, e))?);
+            }
+            height += 1;
+        }
+        Ok(Grid {
+            width: width.unwrap_or(0),
+            height,
+            cells,
+            topology: Topology::Bounded,
+        })
+    }
+
+    pub(crate) fn with_topology(mut self, topology: Topology<T>) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    pub(crate) fn topology(&self) -> &Topology<T> {
+        &self.topology
+    }
+
+    pub(crate) fn width(&self) -> usize {
+        self.width
+    }
+
+    pub(crate) fn height(&self) -> usize {
+        self.height
+    }
+
+    fn index(&self, pos: Point<usize>) -> Option<usize> {
+        if pos.x < self.width && pos.y < self.height {
+            Some(pos.x + pos.y * self.width)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn get(&self, pos: Point<usize>) -> Option<&T> {
+        self.index(pos).map(|i| &self.cells[i])
+    }
+
+    pub(crate) fn get_mut(&mut self, pos: Point<usize>) -> Option<&mut T> {
+        self.index(pos).map(move |i| &mut self.cells[i])
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &T> {
+        self.cells.iter()
+    }
+
+    pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.cells.iter_mut()
+    }
+
+    // All positions, row by row
+    pub(crate) fn positions(&self) -> impl Iterator<Item = Point<usize>> {
+        let width = self.width;
+        (0..self.height).flat_map(move |y| (0..width).map(move |x| Point::new(x, y)))
+    }
+
+    pub(crate) fn rows(&self) -> impl Iterator<Item = &[T]> {
+        // `chunks` panics on a zero size, an empty grid has no rows anyway
+        self.cells.chunks(self.width.max(1))
+    }
+
+    // Cells of column `x`, top to bottom
+    pub(crate) fn column(&self, x: usize) -> impl Iterator<Item = &T> {
+        self.cells.iter().skip(x).step_by(self.width.max(1))
+    }
+
+    // Stored cells up, down, left and right. On a torus the edges wrap
+    // around, otherwise only the cells inside the grid are returned.
+    pub(crate) fn neighbors4(&self, pos: Point<usize>) -> impl Iterator<Item = Point<usize>> {
+        self.offsets(pos, ORTHOGONAL).flatten()
+    }
+
+    // Same as `neighbors4` with the diagonals
+    pub(crate) fn neighbors8(&self, pos: Point<usize>) -> impl Iterator<Item = Point<usize>> {
+        self.offsets(pos, ALL_DIRECTIONS).flatten()
+    }
+
+    // Values up, down, left and right, including the cells past the edges
+    // of an infinite grid
+    pub(crate) fn neighbor_values4(&self, pos: Point<usize>) -> impl Iterator<Item = &T> + '_ {
+        self.offsets(pos, ORTHOGONAL)
+            .filter_map(move |neighbor| self.value(neighbor))
+    }
+
+    // Same as `neighbor_values4` with the diagonals. No day looks at the
+    // diagonal values yet, only the tests do.
+    #[allow(dead_code)]
+    pub(crate) fn neighbor_values8(&self, pos: Point<usize>) -> impl Iterator<Item = &T> + '_ {
+        self.offsets(pos, ALL_DIRECTIONS)
+            .filter_map(move |neighbor| self.value(neighbor))
+    }
+
+    fn value(&self, stored: Option<Point<usize>>) -> Option<&T> {
+        match (stored, &self.topology) {
+            (Some(pos), _) => Some(&self[pos]),
+            (None, Topology::Infinite(outside)) => Some(outside),
+            (None, _) => None,
+        }
+    }
+
+    // The stored cell each offset leads to, if any. On a torus narrower or
+    // shorter than 3 cells, several offsets wrap to the same cell or back to
+    // `pos`, those are only returned once and `pos` never. Neighbors are
+    // looked up in the hot loops of the days, so nothing is allocated.
+    fn offsets(
+        &self,
+        pos: Point<usize>,
+        offsets: &'static [Point<i64>],
+    ) -> impl Iterator<Item = Option<Point<usize>>> {
+        let size = Point::new(self.width as i64, self.height as i64);
+        let wrap = matches!(self.topology, Topology::Torus);
+        let pos = pos.try_cast::<i64>().expect("grid is too large");
+        let mut found = [None; ALL_DIRECTIONS.len()];
+        let mut count = 0;
+        for &offset in offsets {
+            let mut n = pos + offset;
+            if wrap {
+                n = Point::new(n.x.rem_euclid(size.x), n.y.rem_euclid(size.y));
+                if n == pos || found[..count].contains(&n.try_cast()) {
+                    continue;
+                }
+            }
+            let inside = (0..size.x).contains(&n.x) && (0..size.y).contains(&n.y);
+            found[count] = inside.then(|| n.try_cast().unwrap());
+            count += 1;
+        }
+        found.into_iter().take(count)
+    }
+
+    // One line per row, with one character per cell
+    pub(crate) fn render(&self, cell: impl Fn(&T) -> char) -> String {
+        let mut s = String::with_capacity((self.width + 1) * self.height);
+        for row in self.rows() {
+            s.extend(row.iter().map(&cell));
+            s.push('\n');
+        }
+        s
+    }
+}
+
+impl<T: From<u8>> Grid<T> {
+    // Parse a grid of single decimal digits
+    pub(crate) fn parse_digits(input: &str) -> Result<Self> {
+        Self::parse_with(input, |c| {
+            let digit = c.to_digit(10).ok_or(format!("invalid digit {}", c))?;
+            Ok(T::from(digit as u8))
+        })
+    }
+}
+
+// Cells linked to each other without crossing a barrier
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Region {
+    // In row by row order
+    pub(crate) cells: Vec<Point<usize>>,
+    pub(crate) bounds: Bounds,
+}
+
+impl Region {
+    pub(crate) fn size(&self) -> usize {
+        self.cells.len()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Regions {
+    // Index of the region of each cell, `None` for barriers
+    pub(crate) labels: Grid<Option<usize>>,
+    // In the order of their first cell, row by row
+    pub(crate) regions: Vec<Region>,
+}
+
+impl<T> Grid<T> {
+    // Split the cells that are not barriers into regions of cells linked
+    // up, down, left or right, following the topology of the grid.
+    pub(crate) fn regions(&self, barrier: impl Fn(&T) -> bool) -> Regions {
+        let mut sets = UnionFind::new(self.cells.len());
+        for pos in self.positions() {
+            if barrier(&self[pos]) {
+                continue;
+            }
+            for n in self.neighbors4(pos) {
+                if !barrier(&self[n]) {
+                    sets.union(self.index(pos).unwrap(), self.index(n).unwrap());
+                }
+            }
+        }
+
+        let mut labels = Grid::filled(self.width, self.height, None);
+        let mut regions: Vec<Region> = Vec::new();
+        let mut label_of_root = vec![None; self.cells.len()];
+        for pos in self.positions() {
+            if barrier(&self[pos]) {
+                continue;
+            }
+            let root = sets.find(self.index(pos).unwrap());
+            let corner = pos.try_cast().expect("grid is too large");
+            let label = *label_of_root[root].get_or_insert_with(|| {
+                regions.push(Region {
+                    cells: Vec::new(),
+                    bounds: Bounds::new(corner, corner),
+                });
+                regions.len() - 1
+            });
+            regions[label].cells.push(pos);
+            regions[label].bounds.extend(corner);
+            labels[pos] = Some(label);
+        }
+        Regions { labels, regions }
+    }
+}
+
+impl<T> Index<Point<usize>> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, pos: Point<usize>) -> &T {
+        self.get(pos)
+            .unwrap_or_else(|| panic!("{:?} is outside of the grid", pos))
+    }
+}
+
+impl<T> IndexMut<Point<usize>> for Grid<T> {
+    fn index_mut(&mut self, pos: Point<usize>) -> &mut T {
+        self.get_mut(pos)
+            .unwrap_or_else(|| panic!("{:?} is outside of the grid", pos))
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Grid<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in self.rows() {
+            for cell in row {
+                write!(f, "{}", cell)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+// Smallest rectangle holding a set of points, both corners included
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Bounds {
+    pub(crate) min: Point<i64>,
+    pub(crate) max: Point<i64>,
+}
+
+impl Bounds {
+    pub(crate) fn new(min: Point<i64>, max: Point<i64>) -> Self {
+        Bounds { min, max }
+    }
+
+    pub(crate) fn width(&self) -> usize {
+        (self.max.x - self.min.x + 1).max(0) as usize
+    }
+
+    pub(crate) fn height(&self) -> usize {
+        (self.max.y - self.min.y + 1).max(0) as usize
+    }
+
+    fn extend(&mut self, pos: Point<i64>) {
+        self.min = Point::new(self.min.x.min(pos.x), self.min.y.min(pos.y));
+        self.max = Point::new(self.max.x.max(pos.x), self.max.y.max(pos.y));
+    }
+}
+
+// Cells kept in a hash map, for grids that are mostly empty or too large to
+// allocate. Coordinates can be negative.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct SparseGrid<T> {
+    cells: HashMap<Point<i64>, T>,
+    bounds: Option<Bounds>,
+}
+
+impl<T> SparseGrid<T> {
+    pub(crate) fn new() -> Self {
+        SparseGrid {
+            cells: HashMap::new(),
+            bounds: None,
+        }
+    }
+
+    // Number of occupied cells
+    pub(crate) fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    // Rectangle holding all the occupied cells, `None` when there is none
+    pub(crate) fn bounds(&self) -> Option<Bounds> {
+        self.bounds
+    }
+
+    pub(crate) fn get(&self, pos: Point<i64>) -> Option<&T> {
+        self.cells.get(&pos)
+    }
+
+    pub(crate) fn insert(&mut self, pos: Point<i64>, value: T) -> Option<T> {
+        self.track(pos);
+        self.cells.insert(pos, value)
+    }
+
+    // Occupied cells, in no particular order
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (Point<i64>, &T)> {
+        self.cells.iter().map(|(pos, value)| (*pos, value))
+    }
+
+    fn track(&mut self, pos: Point<i64>) {
+        self.bounds.get_or_insert(Bounds::new(pos, pos)).extend(pos);
+    }
+}
+
+impl<T: Default> SparseGrid<T> {
+    // The cell at `pos`, occupied with the default value if it was empty
+    pub(crate) fn entry_or_default(&mut self, pos: Point<i64>) -> &mut T {
+        self.track(pos);
+        self.cells.entry(pos).or_default()
+    }
+}
+
+impl<T: Clone> SparseGrid<T> {
+    // Keep the cells of a dense grid that are not empty. No day reads a
+    // picture back yet, only the round trip test does.
+    #[allow(dead_code)]
+    pub(crate) fn from_dense(grid: &Grid<T>, is_empty: impl Fn(&T) -> bool) -> Self {
+        let mut sparse = SparseGrid::new();
+        for pos in grid.positions() {
+            if !is_empty(&grid[pos]) {
+                sparse.insert(
+                    pos.try_cast().expect("grid is too large"),
+                    grid[pos].clone(),
+                );
+            }
+        }
+        sparse
+    }
+
+    // Dense copy of the cells inside `area`, with `empty` everywhere else.
+    // The top left corner of the area becomes `(0, 0)`.
+    pub(crate) fn to_dense(&self, area: Bounds, empty: T) -> Grid<T> {
+        let mut grid = Grid::filled(area.width(), area.height(), empty);
+        for (pos, value) in self.iter() {
+            if let Some(cell) = (pos - area.min)
+                .try_cast()
+                .and_then(|pos| grid.get_mut(pos))
+            {
+                *cell = value.clone();
+            }
+        }
+        grid
+    }
+}
+
+#[test]
+fn neighbors_stay_inside() {
+    let grid = Grid::filled(3, 2, ());
+    let mut corner: Vec<_> = grid
+        .neighbors8(Point::new(0, 0))
+        .map(|p| (p.x, p.y))
+        .collect();
+    corner.sort();
+    assert_eq!(corner, vec![(0, 1), (1, 0), (1, 1)]);
+    let mut edge: Vec<_> = grid
+        .neighbors4(Point::new(1, 1))
+        .map(|p| (p.x, p.y))
+        .collect();
+    edge.sort();
+    assert_eq!(edge, vec![(0, 1), (1, 0), (2, 1)]);
+}
+
+#[test]
+fn neighbor_values_past_the_edges() {
+    let grid: Grid<u8> = Grid::parse_digits("12\n34\n").unwrap();
+    let mut values: Vec<_> = grid.neighbor_values8(Point::new(0, 0)).copied().collect();
+    values.sort();
+    assert_eq!(values, vec![2, 3, 4]);
+    let grid = grid.with_topology(Topology::Infinite(9));
+    assert_eq!(grid.neighbor_values8(Point::new(0, 0)).count(), 8);
+    assert_eq!(
+        grid.neighbor_values4(Point::new(1, 1)).sum::<u8>(),
+        2 + 3 + 9 + 9
+    );
+}
+
+#[test]
+fn parse_rows_and_columns() {
+    let grid: Grid<u8> = Grid::parse_digits("123\n456\n").unwrap();
+    assert_eq!((grid.width(), grid.height()), (3, 2));
+    assert_eq!(grid[Point::new(2, 0)], 3);
+    assert_eq!(grid.rows().nth(1), Some(&[4, 5, 6][..]));
+    assert_eq!(grid.column(1).collect::<Vec<_>>(), vec![&2, &5]);
+    assert_eq!(grid.to_string(), "123\n456\n");
+    assert_eq!(
+        Grid::<u8>::parse_digits("12\n3\n").unwrap_err().to_string(),
+        "line 2: expected 2 cells"
+    );
+}
+
+#[test]
+fn sparse_bounds_and_dense_round_trip() {
+    let mut sparse = SparseGrid::new();
+    sparse.insert(Point::new(-2, 1), 'a');
+    sparse.insert(Point::new(1, -1), 'b');
+    let bounds = sparse.bounds().unwrap();
+    assert_eq!(bounds, Bounds::new(Point::new(-2, -1), Point::new(1, 1)));
+    let dense = sparse.to_dense(bounds, '.');
+    assert_eq!(dense.to_string(), "...b\n....\na...\n");
+    assert_eq!(sparse.len(), 2);
+    let back = SparseGrid::from_dense(&dense, |c| *c == '.');
+    assert_eq!(back.len(), 2);
+    assert_eq!(back.get(Point::new(0, 2)), Some(&'a'));
+    assert_eq!(back.get(Point::new(0, 0)), None);
+    // Cells outside of the area are left out
+    let corner = Bounds::new(Point::new(0, -1), Point::new(1, 0));
+    assert_eq!(sparse.to_dense(corner, '.').to_string(), ".b\n..\n");
+}
+
+#[test]
+fn regions_split_by_barriers() {
+    let grid: Grid<u8> = Grid::parse_digits("1191\n1919\n9911\n").unwrap();
+    let found = grid.regions(|h| *h == 9);
+    let sizes: Vec<usize> = found.regions.iter().map(Region::size).collect();
+    assert_eq!(sizes, vec![3, 1, 3]);
+    assert_eq!(
+        found.regions[2].bounds,
+        Bounds::new(Point::new(2, 1), Point::new(3, 2))
+    );
+    assert_eq!(found.labels[Point::new(0, 1)], Some(0));
+    assert_eq!(found.labels[Point::new(1, 1)], None);
+    // On a torus the opposite edges touch
+    let found = grid.with_topology(Topology::Torus).regions(|h| *h == 9);
+    assert_eq!(found.regions.len(), 1);
+}
+
+#[test]
+fn small_torus_has_no_repeated_neighbors() {
+    let grid = Grid::filled(2, 1, ()).with_topology(Topology::Torus);
+    let neighbors: Vec<_> = grid.neighbors8(Point::new(0, 0)).collect();
+    assert_eq!(neighbors, vec![Point::new(1, 0)]);
+    let grid = Grid::filled(3, 2, ()).with_topology(Topology::Torus);
+    assert_eq!(grid.neighbors8(Point::new(1, 1)).count(), 5);
+}
@@ -1,28 +1,210 @@
-//mod day01;
-//mod day02;
-//mod day03;
-//mod day04;
-//mod day05;
-//mod day06;
-//mod day07;
-//mod day08;
-//mod day09;
-//mod day11;
-//mod day12;
-//mod day13;
+mod animate;
+mod checkpoint;
+mod cli;
+mod config;
+mod day01;
+mod day02;
+mod day03;
+mod day04;
+mod day05;
+mod day06;
+mod day07;
+mod day08;
+mod day09;
+mod day11;
+mod day12;
+mod day13;
 mod day14;
+mod explain;
+mod graph;
+mod grid;
+mod perf;
+mod point;
+mod point3;
+mod serve;
+mod simulation;
+mod union_find;
 mod util;
 
+use animate::Animation;
+use checkpoint::Session;
+use cli::{Command, RunOptions};
+use config::Config;
+use day01::{Aggregation, WindowComparator};
+use util::Result;
+
+const DAYS: [u32; 13] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 11, 12, 13, 14];
+
 fn main() {
-    //day01::day_01().unwrap();
-    //day02::day_02().unwrap();
-    //day05::day_05().unwrap();
-    //day06::day_06().unwrap();
-    //day07::day_07().unwrap();
-    //day08::day_08().unwrap();
-    //day09::day_09().unwrap();
-    //day11::day_11().unwrap();
-    //day12::day_12().unwrap();
-    //day13::day_13().unwrap();
-    day14::day_14().unwrap();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = cli::parse(&args).and_then(|(command, overrides)| {
+        config::init(Config::load(&overrides)?);
+        match command {
+            Command::Run(options) => run(&options),
+            Command::Serve { port } => serve::serve(port, &DAYS),
+            Command::Bench { day } => perf::bench(day, || run(&RunOptions::new(day))),
+            Command::PerfHistory {
+                day,
+                phase,
+                threshold,
+            } => perf::print_history(day, phase.as_deref(), threshold),
+            Command::Config => {
+                print!("{}", config::get());
+                Ok(())
+            }
+        }
+    });
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(options: &RunOptions) -> Result<()> {
+    if options.animate {
+        return animate(options);
+    }
+    if let Some(criteria) = &options.rating {
+        if options.day != 3 {
+            return Err(format!("day {} has no diagnostic ratings", options.day).into());
+        }
+        return day03::custom_rating(criteria, options.explain);
+    }
+    if options.explain {
+        return explain(options);
+    }
+    if options.stream {
+        return stream(options);
+    }
+    if options.resume.is_some() || options.checkpoint.is_some() || options.steps.is_some() {
+        return simulate(options);
+    }
+    if options.window.is_some() || options.aggregation.is_some() {
+        return analyze(options);
+    }
+    if let Some(target) = options.plan {
+        if options.day != 2 {
+            return Err(format!("day {} has nothing to plan", options.day).into());
+        }
+        return day02::print_plan(target, options.units.unwrap_or(day02::PUZZLE_UNITS));
+    }
+    if options.units.is_some() {
+        return Err("--units is only supported with --plan".into());
+    }
+    if let Some(fleet) = &options.fleet {
+        if options.day != 2 {
+            return Err(format!("day {} has no fleet", options.day).into());
+        }
+        return day02::fleet(fleet, options.safety.unwrap_or(0));
+    }
+    if options.safety.is_some() {
+        return Err("--safety is only supported with --fleet".into());
+    }
+    if options.models.is_some() || options.trajectory.is_some() {
+        return navigate(options);
+    }
+    if options.input.is_some() {
+        return Err("--input is only supported with --stream".into());
+    }
+    if options.wrap {
+        return Err("--wrap is only supported with --steps".into());
+    }
+    match options.day {
+        1 => day01::day_01()?,
+        2 => day02::day_02()?,
+        3 => day03::day_03()?,
+        4 => day04::day_04()?,
+        5 => day05::day_05()?,
+        6 => day06::day_06()?,
+        7 => day07::day_07()?,
+        8 => day08::day_08()?,
+        9 => day09::day_09()?,
+        11 => day11::day_11()?,
+        12 => day12::day_12()?,
+        13 => day13::day_13()?,
+        14 => day14::day_14()?,
+        day => return Err(format!("day {} is not solved", day).into()),
+    }
+    Ok(())
+}
+
+// Run a number of steps of a simulation, possibly starting from and saving to
+// a checkpoint
+fn simulate(options: &RunOptions) -> Result<()> {
+    let session = Session {
+        resume: options.resume.clone(),
+        save_to: options.checkpoint.clone(),
+        steps: options
+            .steps
+            .ok_or("--steps is required to run a simulation")?,
+        every: options.checkpoint_every,
+    };
+    if options.wrap && options.day != 11 {
+        return Err(format!("day {} cannot wrap around", options.day).into());
+    }
+    match options.day {
+        6 => day06::simulate(&session)?,
+        11 => day11::simulate(&session, options.wrap)?,
+        14 => day14::simulate(&session)?,
+        day => return Err(format!("day {} has no resumable simulation", day).into()),
+    }
+    Ok(())
+}
+
+// Compare submarine navigation models, by default the ones the puzzle uses
+fn navigate(options: &RunOptions) -> Result<()> {
+    if options.day != 2 {
+        return Err(format!("day {} has no navigation models", options.day).into());
+    }
+    let export = options.trajectory.as_deref();
+    match &options.models {
+        Some(models) => day02::compare_models(models, export),
+        None => day02::compare_models(&day02::PUZZLE_MODELS, export),
+    }
+}
+
+// Compare sliding windows of the sonar report, by default single readings
+fn analyze(options: &RunOptions) -> Result<()> {
+    if options.day != 1 {
+        return Err(format!("day {} has no window analysis", options.day).into());
+    }
+    day01::analyze(WindowComparator::new(
+        options.window.unwrap_or(1),
+        options.aggregation.unwrap_or(Aggregation::Sum),
+    ))
+}
+
+// Solve without loading the whole input in memory, for very large inputs
+fn stream(options: &RunOptions) -> Result<()> {
+    let name = format!("day{:02}.txt", options.day);
+    let reader = util::open_input(&name, options.input.as_deref())?;
+    match options.day {
+        1 => day01::day_01_streaming(reader)?,
+        2 => day02::day_02_streaming(reader)?,
+        5 => day05::day_05_streaming(reader)?,
+        day => return Err(format!("day {} has no streaming solver", day).into()),
+    }
+    Ok(())
+}
+
+// Print the steps that led to the answer
+fn explain(options: &RunOptions) -> Result<()> {
+    match options.day {
+        3 => day03::explain()?,
+        4 => day04::explain()?,
+        7 => day07::explain()?,
+        day => return Err(format!("day {} cannot explain its answer", day).into()),
+    }
+    Ok(())
+}
+
+fn animate(options: &RunOptions) -> Result<()> {
+    let animation = Animation::new(options.fps);
+    match options.day {
+        6 => day06::animate(&animation)?,
+        11 => day11::animate(&animation)?,
+        13 => day13::animate(&animation)?,
+        day => return Err(format!("day {} has no animation", day).into()),
+    }
+    Ok(())
 }
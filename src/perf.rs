@@ -0,0 +1,354 @@
+use crate::config::{self, OutputFormat};
+use crate::util::{self, Result};
+
+use std::cell::RefCell;
+use std::fs::{self, OpenOptions};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const CSV_HEADER: &str = "commit,timestamp,day,phase,repeat,median_ns,min_ns";
+const TOTAL_PHASE: &str = "total";
+
+type Samples = Vec<(&'static str, Duration)>;
+
+thread_local! {
+    // Phase timings of the current benchmark run, `None` outside of a run
+    static PHASES: RefCell<Option<Samples>> = const { RefCell::new(None) };
+}
+
+// Time a named part of a solution. The time is only kept while a benchmark
+// is running, otherwise this just calls `f`.
+pub(crate) fn phase<T>(name: &'static str, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+    PHASES.with(|phases| {
+        if let Some(samples) = phases.borrow_mut().as_mut() {
+            samples.push((name, elapsed));
+        }
+    });
+    result
+}
+
+fn record(f: impl FnOnce() -> Result<()>) -> Result<Samples> {
+    PHASES.with(|phases| *phases.borrow_mut() = Some(Vec::new()));
+    let result = phase(TOTAL_PHASE, f);
+    let samples = PHASES.with(|phases| phases.borrow_mut().take().unwrap_or_default());
+    result.map(|_| samples)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Measure {
+    commit: String,
+    timestamp: u64,
+    day: u32,
+    phase: String,
+    repeat: u32,
+    median: Duration,
+    min: Duration,
+}
+
+impl Measure {
+    fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{}",
+            self.commit,
+            self.timestamp,
+            self.day,
+            self.phase,
+            self.repeat,
+            self.median.as_nanos(),
+            self.min.as_nanos()
+        )
+    }
+
+    fn parse(line: &str) -> Result<Self> {
+        let fields: Vec<&str> = line.trim().split(',').collect();
+        if fields.len() != 7 {
+            return Err(format!("expected 7 fields: {}", line).into());
+        }
+        Ok(Measure {
+            commit: fields[0].to_string(),
+            timestamp: fields[1].parse()?,
+            day: fields[2].parse()?,
+            phase: fields[3].to_string(),
+            repeat: fields[4].parse()?,
+            median: Duration::from_nanos(fields[5].parse()?),
+            min: Duration::from_nanos(fields[6].parse()?),
+        })
+    }
+}
+
+// Run a day `repeat` times and append the median and best time of each phase
+// to the history file, keyed by the current commit.
+pub(crate) fn bench(day: u32, run: impl Fn() -> Result<()>) -> Result<()> {
+    let config = config::get();
+    let repeat = config.bench_repeat.max(1);
+    let mut runs: Vec<Samples> = Vec::new();
+    for _ in 0..repeat {
+        runs.push(record(&run)?);
+    }
+
+    let commit = current_commit(&std::env::current_dir()?).unwrap_or_else(|| "unknown".into());
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let mut measures = Vec::new();
+    for phase in phase_names(&runs) {
+        let mut times: Vec<Duration> = runs
+            .iter()
+            .flat_map(|samples| samples.iter())
+            .filter(|(name, _)| *name == phase)
+            .map(|(_, d)| *d)
+            .collect();
+        times.sort();
+        measures.push(Measure {
+            commit: commit.clone(),
+            timestamp,
+            day,
+            phase: phase.to_string(),
+            repeat,
+            median: times[times.len() / 2],
+            min: times[0],
+        });
+    }
+
+    append(&config.perf_history, &measures)?;
+    println!("day {} at {}, {} runs:", day, short(&commit), repeat);
+    for m in measures.iter() {
+        println!(
+            "  {:<16} median {:>12?}  min {:>12?}",
+            m.phase, m.median, m.min
+        );
+    }
+    Ok(())
+}
+
+// Names of the phases in the order they first ran
+fn phase_names(runs: &[Samples]) -> Vec<&'static str> {
+    let mut names: Vec<&'static str> = Vec::new();
+    for (name, _) in runs.iter().flat_map(|samples| samples.iter()) {
+        if !names.contains(name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+fn append(path: &Path, measures: &[Measure]) -> Result<()> {
+    let new_file = !path.exists();
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    if new_file {
+        writeln!(file, "{}", CSV_HEADER)?;
+    }
+    for m in measures.iter() {
+        writeln!(file, "{}", m.to_csv())?;
+    }
+    Ok(())
+}
+
+fn read_history(path: &Path) -> Result<Vec<Measure>> {
+    let contents = util::read_file(path.to_str().ok_or("invalid history path")?)
+        .map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && *line != CSV_HEADER)
+        .map(|(i, line)| {
+            Measure::parse(line)
+                .map_err(|e| format!("{} line {}: {}", path.display(), i + 1, e).into())
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq)]
+struct HistoryEntry {
+    commit: String,
+    phase: String,
+    median: Duration,
+    // Relative change from the previous commit, in percent
+    change: Option<f64>,
+    regressed: bool,
+}
+
+// Best median of each commit, in the order the commits were first measured,
+// compared with the commit measured before it.
+fn history(
+    measures: &[Measure],
+    day: u32,
+    phase: Option<&str>,
+    threshold: f64,
+) -> Vec<HistoryEntry> {
+    let mut entries: Vec<HistoryEntry> = Vec::new();
+    for m in measures.iter().filter(|m| m.day == day) {
+        if phase.is_some_and(|p| p != m.phase) {
+            continue;
+        }
+        match entries
+            .iter_mut()
+            .find(|e| e.commit == m.commit && e.phase == m.phase)
+        {
+            Some(entry) => entry.median = entry.median.min(m.median),
+            None => entries.push(HistoryEntry {
+                commit: m.commit.clone(),
+                phase: m.phase.clone(),
+                median: m.median,
+                change: None,
+                regressed: false,
+            }),
+        }
+    }
+
+    let mut previous: Vec<(String, Duration)> = Vec::new();
+    for entry in entries.iter_mut() {
+        if let Some((_, before)) = previous.iter().find(|(p, _)| *p == entry.phase) {
+            let change =
+                (entry.median.as_secs_f64() / before.as_secs_f64().max(f64::EPSILON) - 1.0) * 100.0;
+            entry.change = Some(change);
+            entry.regressed = change > threshold;
+        }
+        previous.retain(|(p, _)| *p != entry.phase);
+        previous.push((entry.phase.clone(), entry.median));
+    }
+    entries
+}
+
+pub(crate) fn print_history(day: u32, phase: Option<&str>, threshold: f64) -> Result<()> {
+    let config = config::get();
+    let measures = read_history(&config.perf_history)?;
+    let entries = history(&measures, day, phase, threshold);
+    if entries.is_empty() {
+        return Err(format!("no measure for day {}", day).into());
+    }
+
+    match config.format {
+        OutputFormat::Text => {
+            println!(
+                "{:<10} {:<16} {:>14} {:>9}",
+                "commit", "phase", "median", "change"
+            );
+            for e in entries.iter() {
+                let change = e.change.map(|c| format!("{:+.1}%", c)).unwrap_or_default();
+                println!(
+                    "{:<10} {:<16} {:>14?} {:>9}{}",
+                    short(&e.commit),
+                    e.phase,
+                    e.median,
+                    change,
+                    if e.regressed { "  REGRESSION" } else { "" }
+                );
+            }
+        }
+        OutputFormat::Csv => {
+            println!("commit,phase,median_ns,change_percent,regressed");
+            for e in entries.iter() {
+                println!(
+                    "{},{},{},{},{}",
+                    e.commit,
+                    e.phase,
+                    e.median.as_nanos(),
+                    e.change.map(|c| format!("{:.2}", c)).unwrap_or_default(),
+                    e.regressed
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn short(commit: &str) -> &str {
+    &commit[..commit.len().min(8)]
+}
+
+// Hash of the checked out commit, read straight from the `.git` directory of
+// `dir` or one of its parents.
+fn current_commit(dir: &Path) -> Option<String> {
+    let git_dir = dir
+        .ancestors()
+        .map(|d| d.join(".git"))
+        .find(|p| p.exists())?;
+    let git_dir = if git_dir.is_file() {
+        // Worktrees and submodules point to the real directory
+        let contents = fs::read_to_string(&git_dir).ok()?;
+        let path = PathBuf::from(contents.trim().strip_prefix("gitdir:")?.trim());
+        git_dir.parent()?.join(path)
+    } else {
+        git_dir
+    };
+
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    match head.strip_prefix("ref:") {
+        Some(reference) => resolve_ref(&git_dir, reference.trim()),
+        None => Some(head.to_string()),
+    }
+}
+
+fn resolve_ref(git_dir: &Path, reference: &str) -> Option<String> {
+    if let Ok(hash) = fs::read_to_string(git_dir.join(reference)) {
+        return Some(hash.trim().to_string());
+    }
+    // Refs may have been packed by `git gc`
+    let packed = fs::read_to_string(git_dir.join("packed-refs")).ok()?;
+    packed
+        .lines()
+        .filter(|line| !line.starts_with('#') && !line.starts_with('^'))
+        .filter_map(|line| line.split_once(' '))
+        .find(|(_, name)| *name == reference)
+        .map(|(hash, _)| hash.to_string())
+}
+
+#[cfg(test)]
+fn measure(commit: &str, phase: &str, median_ms: u64) -> Measure {
+    Measure {
+        commit: commit.to_string(),
+        timestamp: 0,
+        day: 12,
+        phase: phase.to_string(),
+        repeat: 1,
+        median: Duration::from_millis(median_ms),
+        min: Duration::from_millis(median_ms),
+    }
+}
+
+#[test]
+fn history_flags_regressions() {
+    let measures = vec![
+        measure("aaa", "dfs_part2", 100),
+        measure("aaa", "dfs_part1", 10),
+        measure("bbb", "dfs_part2", 105),
+        measure("bbb", "dfs_part2", 104),
+        measure("ccc", "dfs_part2", 150),
+    ];
+    let entries = history(&measures, 12, Some("dfs_part2"), 10.0);
+    let summary: Vec<_> = entries
+        .iter()
+        .map(|e| (e.commit.as_str(), e.median.as_millis(), e.regressed))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![("aaa", 100, false), ("bbb", 104, false), ("ccc", 150, true)]
+    );
+    assert_eq!(entries[0].change, None);
+}
+
+#[test]
+fn measure_csv_round_trip() {
+    let m = measure("0123abcd", "dfs_part2", 42);
+    assert_eq!(Measure::parse(&m.to_csv()).unwrap(), m);
+}
+
+#[test]
+fn resolve_packed_ref() {
+    let dir = std::env::temp_dir().join(format!("aoc-perf-test-{}", std::process::id()));
+    let git_dir = dir.join(".git");
+    fs::create_dir_all(&git_dir).unwrap();
+    fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+    fs::write(
+        git_dir.join("packed-refs"),
+        "# pack-refs with: peeled fully-peeled sorted\n0123456789abcdef refs/heads/main\n",
+    )
+    .unwrap();
+    let commit = current_commit(&dir.join("src"));
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(commit.as_deref(), Some("0123456789abcdef"));
+}
@@ -0,0 +1,179 @@
+use std::error::Error;
+use std::fmt;
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+use std::str::FromStr;
+
+// A position or a displacement on a 2D plane. `y` grows downwards, like the
+// rows of the puzzle inputs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct Point<T> {
+    pub(crate) x: T,
+    pub(crate) y: T,
+}
+
+impl<T> Point<T> {
+    pub(crate) const fn new(x: T, y: T) -> Self {
+        Point { x, y }
+    }
+
+    // Convert both coordinates, `None` if one of them does not fit
+    pub(crate) fn try_cast<U: TryFrom<T>>(self) -> Option<Point<U>> {
+        Some(Point {
+            x: U::try_from(self.x).ok()?,
+            y: U::try_from(self.y).ok()?,
+        })
+    }
+}
+
+impl<T> Point<T>
+where
+    T: Copy + Ord + Add<Output = T> + Sub<Output = T>,
+{
+    // Number of orthogonal steps to reach `other`
+    pub(crate) fn manhattan(self, other: Self) -> T {
+        abs_diff(self.x, other.x) + abs_diff(self.y, other.y)
+    }
+
+    // Number of steps to reach `other` when diagonal moves are allowed
+    pub(crate) fn chebyshev(self, other: Self) -> T {
+        abs_diff(self.x, other.x).max(abs_diff(self.y, other.y))
+    }
+}
+
+// Works for unsigned types too, unlike `(a - b).abs()`
+pub(crate) fn abs_diff<T: Ord + Sub<Output = T>>(a: T, b: T) -> T {
+    if a > b {
+        a - b
+    } else {
+        b - a
+    }
+}
+
+impl Point<i64> {
+    pub(crate) const UP: Self = Point::new(0, -1);
+    pub(crate) const DOWN: Self = Point::new(0, 1);
+    pub(crate) const LEFT: Self = Point::new(-1, 0);
+    pub(crate) const RIGHT: Self = Point::new(1, 0);
+    pub(crate) const ORTHOGONAL: [Self; 4] = [Self::UP, Self::DOWN, Self::LEFT, Self::RIGHT];
+    pub(crate) const DIAGONAL: [Self; 4] = [
+        Point::new(-1, -1),
+        Point::new(1, -1),
+        Point::new(-1, 1),
+        Point::new(1, 1),
+    ];
+    pub(crate) const ALL_DIRECTIONS: [Self; 8] = [
+        Self::UP,
+        Self::DOWN,
+        Self::LEFT,
+        Self::RIGHT,
+        Self::DIAGONAL[0],
+        Self::DIAGONAL[1],
+        Self::DIAGONAL[2],
+        Self::DIAGONAL[3],
+    ];
+
+    // Unit step towards the sign of each coordinate
+    pub(crate) fn signum(self) -> Self {
+        Point::new(self.x.signum(), self.y.signum())
+    }
+}
+
+impl<T: Add<Output = T>> Add for Point<T> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Point::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl<T: Sub<Output = T>> Sub for Point<T> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Point::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl<T: Mul<Output = T> + Copy> Mul<T> for Point<T> {
+    type Output = Self;
+
+    fn mul(self, factor: T) -> Self {
+        Point::new(self.x * factor, self.y * factor)
+    }
+}
+
+impl<T: Neg<Output = T>> Neg for Point<T> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Point::new(-self.x, -self.y)
+    }
+}
+
+impl<T: AddAssign> AddAssign for Point<T> {
+    fn add_assign(&mut self, other: Self) {
+        self.x += other.x;
+        self.y += other.y;
+    }
+}
+
+impl<T: SubAssign> SubAssign for Point<T> {
+    fn sub_assign(&mut self, other: Self) {
+        self.x -= other.x;
+        self.y -= other.y;
+    }
+}
+
+impl<T> From<(T, T)> for Point<T> {
+    fn from((x, y): (T, T)) -> Self {
+        Point { x, y }
+    }
+}
+
+// Parse `x,y`, the format used by the puzzle inputs
+impl<T> FromStr for Point<T>
+where
+    T: FromStr,
+    T::Err: Error + 'static,
+{
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (x, y) = s
+            .trim()
+            .split_once(',')
+            .ok_or_else(|| format!("expected x,y: {}", s))?;
+        Ok(Point::new(x.trim().parse()?, y.trim().parse()?))
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Point<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},{}", self.x, self.y)
+    }
+}
+
+#[test]
+fn arithmetic_and_distances() {
+    let a = Point::new(3, -2);
+    let b: Point<i64> = "-1, 4".parse().unwrap();
+    assert_eq!(a + b, Point::new(2, 2));
+    assert_eq!(b - a, Point::new(-4, 6));
+    assert_eq!((b - a).signum() * 2, Point::new(-2, 2));
+    assert_eq!(a.manhattan(b), 10);
+    assert_eq!(a.chebyshev(b), 6);
+    assert_eq!(Point::new(7usize, 1).manhattan(Point::new(2, 3)), 7);
+    assert!("3;4".parse::<Point<i64>>().is_err());
+}
+
+#[test]
+fn checked_conversion() {
+    assert_eq!(
+        Point::new(2i64, 5).try_cast::<usize>(),
+        Some(Point::new(2, 5))
+    );
+    assert_eq!(
+        (Point::new(0usize, 0).try_cast::<i64>().unwrap() + Point::UP).try_cast::<usize>(),
+        None
+    );
+}
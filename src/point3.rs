@@ -0,0 +1,299 @@
+// Points and boxes in 3D space. No day works in 3D yet, the tests cover them
+// until one does.
+#![allow(dead_code)]
+
+use crate::point::abs_diff;
+use std::error::Error;
+use std::fmt;
+use std::ops::{Add, Index, IndexMut, Neg, Sub};
+use std::str::FromStr;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct Point3<T> {
+    pub(crate) x: T,
+    pub(crate) y: T,
+    pub(crate) z: T,
+}
+
+impl<T> Point3<T> {
+    pub(crate) const fn new(x: T, y: T, z: T) -> Self {
+        Point3 { x, y, z }
+    }
+}
+
+impl<T> Point3<T>
+where
+    T: Copy + Ord + Add<Output = T> + Sub<Output = T>,
+{
+    pub(crate) fn manhattan(self, other: Self) -> T {
+        abs_diff(self.x, other.x) + abs_diff(self.y, other.y) + abs_diff(self.z, other.z)
+    }
+}
+
+// Axes swapped by each permutation, with the sign flip that keeps it a
+// rotation rather than a mirror image
+const PERMUTATIONS: [([usize; 3], i64); 6] = [
+    ([0, 1, 2], 1),
+    ([1, 2, 0], 1),
+    ([2, 0, 1], 1),
+    ([0, 2, 1], -1),
+    ([2, 1, 0], -1),
+    ([1, 0, 2], -1),
+];
+
+impl Point3<i64> {
+    // The point seen from the 24 orientations of the axes: facing any of 6
+    // directions, with any of 4 directions up. The first one is unchanged.
+    pub(crate) fn rotations(self) -> impl Iterator<Item = Self> {
+        PERMUTATIONS.into_iter().flat_map(move |(axes, parity)| {
+            [(1, 1), (1, -1), (-1, 1), (-1, -1)]
+                .into_iter()
+                .map(move |(sx, sy)| {
+                    Point3::new(
+                        sx * self[axes[0]],
+                        sy * self[axes[1]],
+                        sx * sy * parity * self[axes[2]],
+                    )
+                })
+        })
+    }
+}
+
+// Coordinates by axis, 0 for `x` up to 2 for `z`
+impl<T> Index<usize> for Point3<T> {
+    type Output = T;
+
+    fn index(&self, axis: usize) -> &T {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("no axis {} in 3D", axis),
+        }
+    }
+}
+
+impl<T> IndexMut<usize> for Point3<T> {
+    fn index_mut(&mut self, axis: usize) -> &mut T {
+        match axis {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            2 => &mut self.z,
+            _ => panic!("no axis {} in 3D", axis),
+        }
+    }
+}
+
+impl<T: Add<Output = T>> Add for Point3<T> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Point3::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+}
+
+impl<T: Sub<Output = T>> Sub for Point3<T> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Point3::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+impl<T: Neg<Output = T>> Neg for Point3<T> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Point3::new(-self.x, -self.y, -self.z)
+    }
+}
+
+// Parse `x,y,z`
+impl<T> FromStr for Point3<T>
+where
+    T: FromStr,
+    T::Err: Error + 'static,
+{
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let coords: Vec<&str> = s.trim().split(',').collect();
+        match coords[..] {
+            [x, y, z] => Ok(Point3::new(
+                x.trim().parse()?,
+                y.trim().parse()?,
+                z.trim().parse()?,
+            )),
+            _ => Err(format!("expected x,y,z: {}", s).into()),
+        }
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Point3<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},{},{}", self.x, self.y, self.z)
+    }
+}
+
+// Box aligned with the axes, both corners included
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct Cuboid {
+    pub(crate) min: Point3<i64>,
+    pub(crate) max: Point3<i64>,
+}
+
+impl Cuboid {
+    // Smallest cuboid holding both corners, in any order
+    pub(crate) fn new(a: Point3<i64>, b: Point3<i64>) -> Self {
+        Cuboid {
+            min: Point3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)),
+            max: Point3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)),
+        }
+    }
+
+    // Number of integer points inside
+    pub(crate) fn volume(&self) -> i64 {
+        (0..3).map(|a| self.max[a] - self.min[a] + 1).product()
+    }
+
+    pub(crate) fn contains(&self, p: Point3<i64>) -> bool {
+        (0..3).all(|a| self.min[a] <= p[a] && p[a] <= self.max[a])
+    }
+
+    pub(crate) fn intersection(&self, other: &Self) -> Option<Self> {
+        let min = Point3::new(
+            self.min.x.max(other.min.x),
+            self.min.y.max(other.min.y),
+            self.min.z.max(other.min.z),
+        );
+        let max = Point3::new(
+            self.max.x.min(other.max.x),
+            self.max.y.min(other.max.y),
+            self.max.z.min(other.max.z),
+        );
+        (0..3)
+            .all(|a| min[a] <= max[a])
+            .then_some(Cuboid { min, max })
+    }
+
+    // What is left once `other` is cut out, as at most 6 cuboids that don't
+    // overlap
+    pub(crate) fn subtract(&self, other: &Self) -> Vec<Self> {
+        let Some(cut) = self.intersection(other) else {
+            return vec![*self];
+        };
+        let mut pieces = Vec::new();
+        let mut rest = *self;
+        // Slice off what is on either side of the cut, one axis at a time
+        for a in 0..3 {
+            if rest.min[a] < cut.min[a] {
+                let mut piece = rest;
+                piece.max[a] = cut.min[a] - 1;
+                pieces.push(piece);
+                rest.min[a] = cut.min[a];
+            }
+            if rest.max[a] > cut.max[a] {
+                let mut piece = rest;
+                piece.min[a] = cut.max[a] + 1;
+                pieces.push(piece);
+                rest.max[a] = cut.max[a];
+            }
+        }
+        pieces
+    }
+}
+
+// Number of integer points inside any of the cuboids
+pub(crate) fn union_volume(cuboids: &[Cuboid]) -> i64 {
+    let mut disjoint: Vec<Cuboid> = Vec::new();
+    for cuboid in cuboids {
+        disjoint = disjoint.iter().flat_map(|d| d.subtract(cuboid)).collect();
+        disjoint.push(*cuboid);
+    }
+    disjoint.iter().map(Cuboid::volume).sum()
+}
+
+// Parse `x=1..2,y=3..4,z=5..6`, the format used by the puzzle inputs
+impl FromStr for Cuboid {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut a = Point3::default();
+        let mut b = Point3::default();
+        let ranges: Vec<&str> = s.trim().split(',').collect();
+        if ranges.len() != 3 {
+            return Err(format!("expected x=..,y=..,z=..: {}", s).into());
+        }
+        for (axis, (range, name)) in ranges.iter().zip(["x=", "y=", "z="]).enumerate() {
+            let (from, to) = range
+                .trim()
+                .strip_prefix(name)
+                .and_then(|r| r.split_once(".."))
+                .ok_or_else(|| format!("expected {}from..to: {}", name, range))?;
+            a[axis] = from.parse()?;
+            b[axis] = to.parse()?;
+        }
+        Ok(Cuboid::new(a, b))
+    }
+}
+
+impl fmt::Display for Cuboid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "x={}..{},y={}..{},z={}..{}",
+            self.min.x, self.max.x, self.min.y, self.max.y, self.min.z, self.max.z
+        )
+    }
+}
+
+#[test]
+fn point_arithmetic_and_distance() {
+    let a = Point3::new(1, -2, 3);
+    let b: Point3<i64> = "-4, 0, 5".parse().unwrap();
+    assert_eq!(a + b, Point3::new(-3, -2, 8));
+    assert_eq!(b - a, Point3::new(-5, 2, 2));
+    assert_eq!(a.manhattan(b), 9);
+    assert_eq!(-a, Point3::new(-1, 2, -3));
+    assert!("1,2".parse::<Point3<i64>>().is_err());
+}
+
+#[test]
+fn rotations_are_distinct_and_proper() {
+    let p = Point3::new(1, 2, 3);
+    let mut rotated: Vec<_> = p.rotations().collect();
+    assert_eq!(rotated[0], p);
+    rotated.sort();
+    rotated.dedup();
+    assert_eq!(rotated.len(), 24);
+    // Rotating twice is still one of the rotations, a mirror image is not
+    for twice in p.rotations().flat_map(|r| r.rotations()) {
+        assert!(p.rotations().any(|r| r == twice));
+    }
+    assert!(!p.rotations().any(|r| r == Point3::new(-1, 2, 3)));
+}
+
+#[test]
+fn cuboid_intersection_and_subtraction() {
+    let a: Cuboid = "x=10..12,y=10..12,z=10..12".parse().unwrap();
+    let b: Cuboid = "x=11..13,y=11..13,z=11..13".parse().unwrap();
+    assert_eq!(a.volume(), 27);
+    assert_eq!(
+        a.intersection(&b),
+        Some(Cuboid::new(
+            Point3::new(11, 11, 11),
+            Point3::new(12, 12, 12)
+        ))
+    );
+    assert_eq!(union_volume(&[a, b]), 46);
+
+    let pieces = a.subtract(&b);
+    assert_eq!(pieces.iter().map(Cuboid::volume).sum::<i64>(), 27 - 8);
+    assert!(pieces.iter().all(|p| p.intersection(&b).is_none()));
+    assert!(!pieces.iter().any(|p| p.contains(Point3::new(12, 12, 12))));
+
+    let far = Cuboid::new(Point3::new(20, 20, 20), Point3::new(19, 19, 19));
+    assert_eq!(a.intersection(&far), None);
+    assert_eq!(a.subtract(&far), vec![a]);
+    assert_eq!(a.to_string().parse::<Cuboid>().unwrap(), a);
+}
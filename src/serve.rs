@@ -0,0 +1,409 @@
+use crate::animate::Frame;
+use crate::config;
+use crate::util::{self, Result};
+use crate::{day06, day11, day13};
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, prelude::*, BufReader};
+use std::net::{TcpListener, TcpStream};
+use std::process;
+use std::time::{Duration, Instant};
+
+const ANSWERS_FILE: &str = "answers.txt";
+const ANIMATION_FPS: u32 = 10;
+const ANIMATED_DAYS: [u32; 3] = [6, 11, 13];
+
+#[derive(Debug, PartialEq)]
+enum Verified {
+    Ok,
+    Mismatch,
+    Unknown,
+}
+
+#[derive(Debug)]
+struct DayResult {
+    day: u32,
+    success: bool,
+    elapsed: Duration,
+    answers: Vec<String>,
+    verified: Verified,
+}
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    location: Option<&'static str>,
+    body: String,
+}
+
+impl Response {
+    fn html(body: String) -> Self {
+        Response {
+            status: "200 OK",
+            content_type: "text/html; charset=utf-8",
+            location: None,
+            body,
+        }
+    }
+
+    fn not_found() -> Self {
+        Response {
+            status: "404 Not Found",
+            content_type: "text/plain; charset=utf-8",
+            location: None,
+            body: "not found\n".to_string(),
+        }
+    }
+
+    fn redirect_home() -> Self {
+        Response {
+            status: "303 See Other",
+            content_type: "text/plain; charset=utf-8",
+            location: Some("/"),
+            body: String::new(),
+        }
+    }
+
+    fn write_to(&self, stream: &mut impl Write) -> io::Result<()> {
+        write!(stream, "HTTP/1.1 {}\r\n", self.status)?;
+        if let Some(location) = self.location {
+            write!(stream, "Location: {}\r\n", location)?;
+        }
+        write!(
+            stream,
+            "Content-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.content_type,
+            self.body.len(),
+            self.body
+        )
+    }
+}
+
+struct Dashboard {
+    days: Vec<u32>,
+    results: Vec<DayResult>,
+}
+
+impl Dashboard {
+    fn new(days: &[u32]) -> Self {
+        Dashboard {
+            days: days.to_vec(),
+            results: Vec::new(),
+        }
+    }
+
+    // Run every day in a child process, so each one gets its own timing and
+    // its output can be checked against the expected answers.
+    fn refresh(&mut self) -> Result<()> {
+        let expected = read_expected_answers()?;
+        let exe = std::env::current_exe()?;
+        self.results = Vec::new();
+        for &day in self.days.iter() {
+            eprintln!("running day {}", day);
+            let start = Instant::now();
+            let output = process::Command::new(&exe)
+                .arg("--resources")
+                .arg(&config::get().resources)
+                .args(["run", &day.to_string()])
+                .output()?;
+            let elapsed = start.elapsed();
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            self.results.push(DayResult {
+                day,
+                success: output.status.success(),
+                elapsed,
+                answers: answer_lines(&stdout, expected.get(&day)),
+                verified: verify(&stdout, expected.get(&day)),
+            });
+        }
+        Ok(())
+    }
+
+    fn route(&mut self, path: &str) -> Response {
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        match segments.as_slice() {
+            [""] => Response::html(self.render()),
+            ["refresh"] => match self.refresh() {
+                Ok(()) => Response::redirect_home(),
+                Err(e) => Response::html(page("error", &escape(&e.to_string()))),
+            },
+            ["day", day, "grid"] => match day_frames(day) {
+                Some(frames) => Response::html(page(
+                    &format!("day {} grid", day),
+                    &frame_html(frames.last().unwrap()),
+                )),
+                None => Response::not_found(),
+            },
+            ["day", day, "animation"] => match day_frames(day) {
+                Some(frames) => Response::html(animation_page(day, &frames)),
+                None => Response::not_found(),
+            },
+            _ => Response::not_found(),
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut rows = String::new();
+        for result in self.results.iter() {
+            let links = if ANIMATED_DAYS.contains(&result.day) {
+                format!(
+                    "<a href=\"/day/{0}/grid\">grid</a> <a href=\"/day/{0}/animation\">animation</a>",
+                    result.day
+                )
+            } else {
+                String::new()
+            };
+            rows.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{:.1} ms</td><td>{:?}</td><td><pre>{}</pre></td><td>{}</td></tr>\n",
+                result.day,
+                if result.success { "ok" } else { "failed" },
+                result.elapsed.as_secs_f64() * 1000.0,
+                result.verified,
+                escape(&result.answers.join("\n")),
+                links
+            ));
+        }
+        let body = format!(
+            "<p><a href=\"/refresh\">run again</a></p>\n<table>\n<tr><th>day</th><th>status</th><th>time</th><th>verified</th><th>answers</th><th></th></tr>\n{}</table>",
+            rows
+        );
+        page(&format!("Advent of Code {}", config::get().year), &body)
+    }
+}
+
+pub(crate) fn serve(port: u16, days: &[u32]) -> Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    let mut dashboard = Dashboard::new(days);
+    dashboard.refresh()?;
+    println!("Serving on http://{}", listener.local_addr()?);
+    for stream in listener.incoming() {
+        if let Err(e) = handle(stream?, &mut dashboard) {
+            eprintln!("request failed: {}", e);
+        }
+    }
+    Ok(())
+}
+
+fn handle(mut stream: TcpStream, dashboard: &mut Dashboard) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Skip the headers, nothing in them is needed
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let response = match request_line.split(' ').collect::<Vec<_>>().as_slice() {
+        ["GET", target, _] => {
+            let path = target.split('?').next().unwrap_or("/");
+            dashboard.route(path)
+        }
+        _ => Response::not_found(),
+    };
+    response.write_to(&mut stream)
+}
+
+fn read_expected_answers() -> Result<HashMap<u32, Vec<String>>> {
+    let contents = match util::read_input(ANSWERS_FILE) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut expected = HashMap::new();
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (day, answers) = line
+            .split_once(':')
+            .ok_or_else(|| format!("invalid line in {}: {}", ANSWERS_FILE, line))?;
+        let day = day.trim().parse::<u32>()?;
+        expected.insert(day, answers.split_whitespace().map(String::from).collect());
+    }
+    Ok(expected)
+}
+
+// Answers have to appear as whole values, so 566 does not match inside 15662
+fn values(output: &str) -> HashSet<&str> {
+    output
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
+fn verify(output: &str, expected: Option<&Vec<String>>) -> Verified {
+    let values = values(output);
+    match expected {
+        None => Verified::Unknown,
+        Some(answers) if answers.iter().all(|a| values.contains(a.as_str())) => Verified::Ok,
+        Some(_) => Verified::Mismatch,
+    }
+}
+
+// The lines holding an expected answer, wherever the day prints them. Without
+// expectations the last lines are the best guess.
+fn answer_lines(output: &str, expected: Option<&Vec<String>>) -> Vec<String> {
+    let Some(answers) = expected else {
+        return last_lines(output, 2);
+    };
+    output
+        .lines()
+        .map(str::trim)
+        .filter(|l| {
+            let values = values(l);
+            answers.iter().any(|a| values.contains(a.as_str()))
+        })
+        .map(String::from)
+        .collect()
+}
+
+fn last_lines(output: &str, count: usize) -> Vec<String> {
+    let lines: Vec<&str> = output
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect();
+    lines[lines.len().saturating_sub(count)..]
+        .iter()
+        .map(|l| l.to_string())
+        .collect()
+}
+
+fn day_frames(day: &str) -> Option<Vec<Frame>> {
+    let day = day.parse::<u32>().ok()?;
+    let contents = util::read_input(&format!("day{:02}.txt", day)).ok()?;
+    match day {
+        6 => Some(day06::frames(&contents, 256).collect()),
+        11 => Some(day11::frames(&contents).collect()),
+        13 => day13::frames(&contents).ok(),
+        _ => None,
+    }
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{0}</title>\n<style>body {{ font-family: monospace; }} b {{ color: #d90; }} td {{ padding: 0 1em; vertical-align: top; }}</style>\n</head><body><h1>{0}</h1>\n{1}\n</body></html>\n",
+        escape(title),
+        body
+    )
+}
+
+fn frame_html(frame: &Frame) -> String {
+    let mut body = String::new();
+    for c in frame.body.chars() {
+        if c == frame.highlight {
+            body.push_str("<b>");
+            body.push_str(&escape(&c.to_string()));
+            body.push_str("</b>");
+        } else {
+            body.push_str(&escape(&c.to_string()));
+        }
+    }
+    format!("<p>{}</p><pre>{}</pre>", escape(&frame.caption), body)
+}
+
+// All the frames are embedded in the page, so the animation needs nothing
+// but the browser.
+fn animation_page(day: &str, frames: &[Frame]) -> String {
+    let frames = frames
+        .iter()
+        .map(|f| js_string(&frame_html(f)))
+        .collect::<Vec<_>>()
+        .join(",\n");
+    let body = format!(
+        "<div id=\"frame\"></div>\n<script>\nconst frames = [\n{}\n];\nlet i = 0;\nconst show = () => {{ document.getElementById(\"frame\").innerHTML = frames[i]; i = (i + 1) % frames.length; }};\nshow();\nsetInterval(show, {});\n</script>",
+        frames,
+        1000 / ANIMATION_FPS
+    );
+    page(&format!("day {} animation", day), &body)
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn js_string(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '<' => quoted.push_str("\\u003c"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+fn get(listener: &TcpListener, dashboard: &mut Dashboard, path: &str) -> String {
+    let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    write!(client, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+    let (stream, _) = listener.accept().unwrap();
+    handle(stream, dashboard).unwrap();
+    let mut response = String::new();
+    client.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn serve_dashboard_and_grid() {
+    let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+    let mut dashboard = Dashboard::new(&[11]);
+    dashboard.results.push(DayResult {
+        day: 11,
+        success: true,
+        elapsed: Duration::from_millis(3),
+        answers: vec!["Total flash: 1594".to_string()],
+        verified: verify("Total flash: 1594", Some(&vec!["1594".to_string()])),
+    });
+
+    let response = get(&listener, &mut dashboard, "/");
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.contains("Total flash: 1594"));
+    assert!(response.contains("<td>Ok</td>"));
+    assert!(response.contains("/day/11/animation"));
+
+    let response = get(&listener, &mut dashboard, "/day/11/grid");
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.contains("<b>0</b>"));
+
+    let response = get(&listener, &mut dashboard, "/day/12/grid");
+    assert!(response.starts_with("HTTP/1.1 404 Not Found"));
+}
+
+#[test]
+fn js_string_cannot_close_script() {
+    assert_eq!(js_string("</script>\n\""), "\"\\u003c/script>\\n\\\"\"");
+}
+
+#[test]
+fn verify_matches_whole_values() {
+    let expected = vec!["566".to_string(), "134862".to_string()];
+    assert_eq!(
+        verify("risk: 566\nSome(134862)", Some(&expected)),
+        Verified::Ok
+    );
+    assert_eq!(
+        verify("risk: 15662\n134862", Some(&expected)),
+        Verified::Mismatch
+    );
+    assert_eq!(verify("anything", None), Verified::Unknown);
+}
+
+#[test]
+fn answers_are_found_anywhere_in_the_output() {
+    let expected = vec!["1121".to_string(), "1065".to_string()];
+    let output = "increases: 1121\nwindow increases: 1065\ngap at 15662\n";
+    assert_eq!(
+        answer_lines(output, Some(&expected)),
+        vec!["increases: 1121", "window increases: 1065"]
+    );
+    assert_eq!(answer_lines("a\nb\nc\n", None), vec!["b", "c"]);
+}
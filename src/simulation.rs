@@ -0,0 +1,194 @@
+use crate::util;
+use std::fmt;
+
+// A state that evolves one step at a time, the same way every time
+pub(crate) trait Simulation: fmt::Display {
+    type Fingerprint: Eq;
+
+    // Fails when the state can't be represented anymore, e.g. on overflow
+    fn step(&mut self) -> util::Result<()>;
+
+    // What makes two states behave the same from now on. Counters such as
+    // the number of steps run so far should be left out.
+    fn fingerprint(&self) -> Self::Fingerprint;
+}
+
+// Steps after which the states repeat forever: the state after `start +
+// period` steps is the same as the one after `start` steps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Cycle {
+    pub(crate) start: usize,
+    pub(crate) period: usize,
+}
+
+impl Cycle {
+    // The same cycle, counting steps from `steps` steps earlier
+    pub(crate) fn offset(&self, steps: usize) -> Self {
+        Cycle {
+            start: self.start + steps,
+            period: self.period,
+        }
+    }
+
+    // Fewest steps giving the same state as `steps` steps
+    pub(crate) fn reduce(&self, steps: usize) -> usize {
+        if steps < self.start {
+            steps
+        } else {
+            self.start + (steps - self.start) % self.period
+        }
+    }
+}
+
+impl fmt::Display for Cycle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "cycle of {} steps starting at step {}",
+            self.period, self.start
+        )
+    }
+}
+
+pub(crate) fn run<S: Simulation>(sim: &mut S, steps: usize) -> util::Result<()> {
+    for _ in 0..steps {
+        sim.step()?;
+    }
+    Ok(())
+}
+
+// Step until `done` holds, returning the number of steps run. Gives up after
+// `limit` steps.
+pub(crate) fn run_until<S: Simulation>(
+    sim: &mut S,
+    limit: usize,
+    done: impl Fn(&S) -> bool,
+) -> util::Result<Option<usize>> {
+    for steps in 0..=limit {
+        if done(sim) {
+            return Ok(Some(steps));
+        }
+        if steps < limit {
+            sim.step()?;
+        }
+    }
+    Ok(None)
+}
+
+// Brent's cycle detection, which only keeps two states around. Gives up after
+// `limit` steps, some simulations never repeat.
+pub(crate) fn find_cycle<S: Simulation + Clone>(
+    initial: &S,
+    limit: usize,
+) -> util::Result<Option<Cycle>> {
+    // Find the period, looking for the hare in windows of doubling size
+    let mut power = 1;
+    let mut period = 1;
+    let mut tortoise = initial.clone();
+    let mut hare = initial.clone();
+    hare.step()?;
+    let mut steps = 1;
+    while tortoise.fingerprint() != hare.fingerprint() {
+        if steps >= limit {
+            return Ok(None);
+        }
+        if power == period {
+            tortoise = hare.clone();
+            power *= 2;
+            period = 0;
+        }
+        hare.step()?;
+        period += 1;
+        steps += 1;
+    }
+
+    // Walk two states `period` steps apart until they meet at the start
+    let mut tortoise = initial.clone();
+    let mut hare = initial.clone();
+    run(&mut hare, period)?;
+    let mut start = 0;
+    while tortoise.fingerprint() != hare.fingerprint() {
+        tortoise.step()?;
+        hare.step()?;
+        start += 1;
+    }
+    Ok(Some(Cycle { start, period }))
+}
+
+// State after `steps` steps, skipping whole cycles when the states repeat
+// within `limit` steps. Counters in the state only count the steps run.
+pub(crate) fn fast_forward<S: Simulation + Clone>(
+    initial: &S,
+    steps: usize,
+    limit: usize,
+) -> util::Result<S> {
+    let steps = match find_cycle(initial, limit.min(steps))? {
+        Some(cycle) => cycle.reduce(steps),
+        None => steps,
+    };
+    let mut sim = initial.clone();
+    run(&mut sim, steps)?;
+    Ok(sim)
+}
+
+#[cfg(test)]
+#[derive(Clone)]
+struct Counter {
+    value: u32,
+    modulo: u32,
+}
+
+#[cfg(test)]
+impl fmt::Display for Counter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+#[cfg(test)]
+impl Simulation for Counter {
+    type Fingerprint = u32;
+
+    // Counts up to `modulo` then wraps to 3
+    fn step(&mut self) -> util::Result<()> {
+        self.value = if self.value + 1 == self.modulo {
+            3
+        } else {
+            self.value + 1
+        };
+        Ok(())
+    }
+
+    fn fingerprint(&self) -> u32 {
+        self.value
+    }
+}
+
+#[test]
+fn cycle_start_and_period() {
+    let counter = Counter {
+        value: 0,
+        modulo: 10,
+    };
+    let cycle = find_cycle(&counter, 100).unwrap().unwrap();
+    assert_eq!(
+        cycle,
+        Cycle {
+            start: 3,
+            period: 7
+        }
+    );
+    assert_eq!(find_cycle(&counter, 5).unwrap(), None);
+    let far = fast_forward(&counter, 1_000_000_000_000, 100).unwrap();
+    assert_eq!(far.value, 3 + ((1_000_000_000_000usize - 3) % 7) as u32);
+
+    let mut counter = counter;
+    assert_eq!(
+        run_until(&mut counter, 100, |c| c.value == 5).unwrap(),
+        Some(5)
+    );
+    assert_eq!(
+        run_until(&mut counter, 100, |c| c.value == 1).unwrap(),
+        None
+    );
+}
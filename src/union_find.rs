@@ -0,0 +1,54 @@
+// Disjoint sets of `0..len`, merged with `union`. Each set is represented by
+// one of its members, returned by `find`.
+#[derive(Debug, Clone)]
+pub(crate) struct UnionFind {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl UnionFind {
+    pub(crate) fn new(len: usize) -> Self {
+        UnionFind {
+            parent: (0..len).collect(),
+            size: vec![1; len],
+        }
+    }
+
+    pub(crate) fn find(&mut self, mut x: usize) -> usize {
+        while self.parent[x] != x {
+            // Path halving keeps the trees flat
+            self.parent[x] = self.parent[self.parent[x]];
+            x = self.parent[x];
+        }
+        x
+    }
+
+    // Merge the sets of `a` and `b`, returning false if they already were
+    // the same set
+    pub(crate) fn union(&mut self, a: usize, b: usize) -> bool {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return false;
+        }
+        // Attach the smaller tree under the bigger one
+        let (big, small) = if self.size[a] >= self.size[b] {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        self.parent[small] = big;
+        self.size[big] += self.size[small];
+        true
+    }
+}
+
+#[test]
+fn union_and_find() {
+    let mut sets = UnionFind::new(5);
+    assert!(sets.union(0, 1));
+    assert!(sets.union(3, 4));
+    assert!(sets.union(1, 4));
+    assert!(!sets.union(0, 3));
+    assert_eq!(sets.find(0), sets.find(3));
+    assert_ne!(sets.find(2), sets.find(0));
+}
@@ -1,6 +1,7 @@
 use std::error::Error;
 use std::fs::File;
-use std::io::prelude::*;
+use std::io::{prelude::*, BufReader};
+use std::path::Path;
 
 #[allow(dead_code)]
 pub fn read_file(file: &str) -> std::io::Result<String> {
@@ -11,4 +12,48 @@ pub fn read_file(file: &str) -> std::io::Result<String> {
     Ok(contents)
 }
 
+// Read a puzzle input from the configured resources directory
+pub fn read_input(name: &str) -> std::io::Result<String> {
+    let path = crate::config::get().resources.join(name);
+    let mut file = File::open(&path)
+        .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+
+    Ok(contents)
+}
+
+// Open a puzzle input to read it line by line. `path` replaces the file from
+// the resources directory when given.
+pub fn open_input(name: &str, path: Option<&Path>) -> std::io::Result<BufReader<File>> {
+    let path = match path {
+        Some(path) => path.to_path_buf(),
+        None => crate::config::get().resources.join(name),
+    };
+    let file = File::open(&path)
+        .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+    Ok(BufReader::new(file))
+}
+
+// Call `f` with every non-empty line and its number, reusing the same buffer
+// so memory use does not grow with the size of the input.
+pub(crate) fn for_each_line<R: BufRead>(
+    mut reader: R,
+    mut f: impl FnMut(usize, &str) -> Result<()>,
+) -> Result<()> {
+    let mut line = String::new();
+    let mut line_number = 0;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        line_number += 1;
+        let trimmed = line.trim();
+        if !trimmed.is_empty() {
+            f(line_number, trimmed).map_err(|e| format!("line {}: {}", line_number, e))?;
+        }
+    }
+}
+
 pub(crate) type Result<T> = std::result::Result<T, Box<dyn Error>>;